@@ -1,11 +1,52 @@
-use near_sdk::{Gas, StorageUsage};
+use near_sdk::{Balance, Gas, StorageUsage};
 
 /// Currently, we allow three different types of NFTs per Creator, namely
 /// Common, Uncommon, Rare
 pub const NFT_RANKING: usize = 3;
 /// The storage cost of an AccountId type (8 bytes)
 pub const ACCOUNT_ID_STORAGE_COST: StorageUsage = 8;
-/// The storage cost of creator registry 2 * AccountId + CreatorMetadata (which we allow to be at least 1kb)
-pub const CREATOR_REGISTRY_STORAGE_COST: StorageUsage = 1_016;
+/// The maximum length, in bytes, of the title of a creator NFT
+pub const MAX_NFT_TITLE_LENGTH: usize = 128;
+/// The maximum length, in bytes, of the description, or extra, of a creator NFT
+pub const MAX_NFT_TEXT_LENGTH: usize = 1_024;
+/// The maximum length, in bytes, of the media, or reference, URL of a creator NFT
+pub const MAX_NFT_URL_LENGTH: usize = 512;
+/// The URL schemes accepted for the media, and reference, of a creator NFT
+pub const NFT_URL_SCHEMES: [&str; 3] = ["https://", "ipfs://", "ar://"];
+/// The default maximum size, in bytes, of the serialized metadata of a creator registration
+pub const DEFAULT_MAX_CREATOR_METADATA_SIZE: StorageUsage = 4_096;
 /// The gas cost of a fungible token transfer
 pub const GAS_FOR_FT_TRANSFER: Gas = Gas(20_000_000_000_000u64);
+/// The gas cost of a NEP-145 `storage_balance_of` query on a fungible token contract
+pub const GAS_FOR_FT_STORAGE_CHECK: Gas = Gas(5_000_000_000_000u64);
+/// The gas cost of a NEP-145 `storage_deposit` on a fungible token contract
+pub const GAS_FOR_FT_STORAGE_DEPOSIT: Gas = Gas(10_000_000_000_000u64);
+/// The gas reserved to withdraw once the creator storage registration is checked, i.e. an optional
+/// storage deposit, along with the transfer and its callback
+pub const GAS_FOR_WITHDRAW_AFTER_STORAGE_CHECK: Gas = Gas(80_000_000_000_000u64);
+/// The gas reserved to withdraw once the creator storage is registered on the fungible token contract,
+/// i.e. the transfer and its callback
+pub const GAS_FOR_WITHDRAW_AFTER_STORAGE_DEPOSIT: Gas = Gas(50_000_000_000_000u64);
+/// The NEAR deposited on behalf of a creator, to register its storage on a fungible token contract.
+/// Creator registrations reserve it for each fungible token their tiers are priced in
+pub const FT_STORAGE_DEPOSIT: Balance = 1_250_000_000_000_000_000_000;
+/// The gas reserved to settle a creator funding entry, i.e. to mint its NFT and credit the creator
+pub const GAS_FOR_SETTLEMENT_ENTRY: Gas = Gas(10_000_000_000_000u64);
+/// The sentinel fungible token id of native NEAR, to be used in NFT tier prices
+/// and protocol allowed tokens fees, for pledges made via `pledge_near`. The `system`
+/// account is reserved by the protocol, so no token contract can ever be deployed there
+pub const NEAR_TOKEN_ID: &str = "system";
+/// An upper bound of the bytes used by the first pledge of a backer in an epoch, i.e. its funds
+/// and storage usage entries, which the backer storage balance has to cover before pledging
+pub const MAX_PLEDGE_STORAGE_USAGE: StorageUsage = 1_024;
+/// An upper bound of the bytes used by a single pledge entry, i.e. the backer and creator
+/// funding entries, which the backer storage balance has to cover before pledging
+pub const MAX_PLEDGE_ENTRY_STORAGE_USAGE: StorageUsage = 512;
+/// The current version of the `ft_on_transfer` pledge message schema
+pub const PLEDGE_MESSAGE_VERSION: u8 = 1;
+/// The maximum number of NFTs a user can pledge for, in a single transfer
+pub const MAX_PLEDGE_QUANTITY: u32 = 10;
+/// The denominator of fees expressed in basis points, i.e. 10_000 basis points are 100%
+pub const BASIS_POINTS_DENOMINATOR: u128 = 10_000;
+/// The maximum protocol fee, in basis points, the admin can set per fungible token (10%)
+pub const MAX_PROTOCOL_FEE_BPS: u16 = 1_000;
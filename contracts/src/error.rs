@@ -33,6 +33,76 @@ pub enum MetaDaoError {
     InvalidFTTokenId,
     #[error("Uncovered storage costs")]
     UncoveredStorageCosts,
+    #[error("Funds are not refundable for creator in epoch")]
+    FundsNotRefundable,
+    #[error("Funds are refundable for creator in epoch, and cannot be released")]
+    FundsAreRefundable,
+    #[error("No funds to refund")]
+    NoFundsToRefund,
+    #[error("Already in minting period")]
+    AlreadyInMinting,
+    #[error("Funding period is not over")]
+    FundingIsNotOver,
+    #[error("Creator funding goal was not met")]
+    FundingGoalNotMet,
+    #[error("Invalid funding goal")]
+    InvalidFundingGoal,
+    #[error("Invalid epoch schedule")]
+    InvalidEpochSchedule,
+    #[error("Epoch phases are scheduled, and cannot be manually changed")]
+    EpochIsScheduled,
+    #[error("Not in minting period")]
+    NotInMintingPeriod,
+    #[error("Invalid epoch phase transition")]
+    InvalidPhaseTransition,
+    #[error("Invalid pledge message")]
+    InvalidPledgeMessage,
+    #[error("Unsupported pledge message version")]
+    UnsupportedPledgeMessageVersion,
+    #[error("User did not provide enough funds to obtain the chosen NFT")]
+    InsufficientPledgeAmount,
+    #[error("Protocol fee exceeds the maximum allowed fee")]
+    InvalidProtocolFee,
+    #[error("Arithmetic overflow")]
+    ArithmeticOverflow,
+    #[error("Not enough accrued protocol fees")]
+    InsufficientProtocolFees,
+    #[error("Not enough claimable balance")]
+    InsufficientClaimableBalance,
+    #[error("Fungible token storage reserve is still needed by the creator")]
+    FTStorageReserveInUse,
+    #[error("Account storage balance does not cover the storage costs")]
+    InsufficientStorageBalance,
+    #[error("Epoch cannot be archived")]
+    EpochCannotBeArchived,
+    #[error("Creator metadata exceeds the maximum size")]
+    CreatorMetadataTooLarge,
+    #[error("Creator is already registered for current epoch")]
+    CreatorAlreadyRegistered,
+    #[error("Creator metadata must provide a Common, Uncommon and Rare entry per field, in order")]
+    InvalidCreatorMetadataTiers,
+    #[error("NFT title cannot be empty")]
+    EmptyNFTTitle,
+    #[error("NFT copies must be positive")]
+    InvalidNFTCopies,
+    #[error("NFT prices must be positive, in at least one fungible token")]
+    InvalidNFTPrice,
+    #[error("NFT media and reference must be valid URLs")]
+    InvalidNFTUrl,
+    #[error("NFT metadata field exceeds the maximum length")]
+    NFTMetadataFieldTooLong,
+    #[error("Epoch has no legacy creators to migrate")]
+    NoLegacyCreatorsToMigrate,
+    #[error("NFT tier is sold out")]
+    TierSoldOut,
+    #[error("No NFT to claim")]
+    NoNFTToClaim,
+    #[error("Only the creator, the admin or the settlement operator can settle the creator")]
+    InvalidSettlementCall,
+    #[error("Creator is already settled")]
+    CreatorAlreadySettled,
+    #[error("Funding entries were already minted or credited, and cannot be refunded")]
+    SettlementAlreadyStarted,
 }
 
 impl AsRef<str> for MetaDaoError {
@@ -56,6 +126,55 @@ impl AsRef<str> for MetaDaoError {
             Self::InvalidInitializationOfEpoch => "Invalid initialization of epoch",
             Self::InvalidFTTokenId => "Invalid Fungible token id",
             Self::UncoveredStorageCosts => "Uncovered storage costs",
+            Self::FundsNotRefundable => "Funds are not refundable for creator in epoch",
+            Self::FundsAreRefundable => {
+                "Funds are refundable for creator in epoch, and cannot be released"
+            }
+            Self::NoFundsToRefund => "No funds to refund",
+            Self::AlreadyInMinting => "Already in minting period",
+            Self::FundingIsNotOver => "Funding period is not over",
+            Self::FundingGoalNotMet => "Creator funding goal was not met",
+            Self::InvalidFundingGoal => "Invalid funding goal",
+            Self::InvalidEpochSchedule => "Invalid epoch schedule",
+            Self::EpochIsScheduled => "Epoch phases are scheduled, and cannot be manually changed",
+            Self::NotInMintingPeriod => "Not in minting period",
+            Self::InvalidPhaseTransition => "Invalid epoch phase transition",
+            Self::InvalidPledgeMessage => "Invalid pledge message",
+            Self::UnsupportedPledgeMessageVersion => "Unsupported pledge message version",
+            Self::InsufficientPledgeAmount => {
+                "User did not provide enough funds to obtain the chosen NFT"
+            }
+            Self::InvalidProtocolFee => "Protocol fee exceeds the maximum allowed fee",
+            Self::ArithmeticOverflow => "Arithmetic overflow",
+            Self::InsufficientProtocolFees => "Not enough accrued protocol fees",
+            Self::InsufficientClaimableBalance => "Not enough claimable balance",
+            Self::FTStorageReserveInUse => {
+                "Fungible token storage reserve is still needed by the creator"
+            }
+            Self::InsufficientStorageBalance => {
+                "Account storage balance does not cover the storage costs"
+            }
+            Self::EpochCannotBeArchived => "Epoch cannot be archived",
+            Self::CreatorMetadataTooLarge => "Creator metadata exceeds the maximum size",
+            Self::CreatorAlreadyRegistered => "Creator is already registered for current epoch",
+            Self::InvalidCreatorMetadataTiers => {
+                "Creator metadata must provide a Common, Uncommon and Rare entry per field, in order"
+            }
+            Self::EmptyNFTTitle => "NFT title cannot be empty",
+            Self::InvalidNFTCopies => "NFT copies must be positive",
+            Self::InvalidNFTPrice => "NFT prices must be positive, in at least one fungible token",
+            Self::InvalidNFTUrl => "NFT media and reference must be valid URLs",
+            Self::NFTMetadataFieldTooLong => "NFT metadata field exceeds the maximum length",
+            Self::NoLegacyCreatorsToMigrate => "Epoch has no legacy creators to migrate",
+            Self::TierSoldOut => "NFT tier is sold out",
+            Self::NoNFTToClaim => "No NFT to claim",
+            Self::InvalidSettlementCall => {
+                "Only the creator, the admin or the settlement operator can settle the creator"
+            }
+            Self::CreatorAlreadySettled => "Creator is already settled",
+            Self::SettlementAlreadyStarted => {
+                "Funding entries were already minted or credited, and cannot be refunded"
+            }
         }
     }
 }
@@ -0,0 +1,213 @@
+use std::collections::HashMap;
+
+use near_sdk::json_types::U128;
+use near_sdk::serde::Serialize;
+use near_sdk::{env, serde_json, AccountId};
+
+use crate::{
+    nft::UserNFTRank, phase::EpochPhase, schedule::EpochSchedule, CreatorAccountId, FTAccountId,
+    UserAccountId,
+};
+
+/// The NEP-297 standard name of MetaStage events
+pub const METASTAGE_STANDARD_NAME: &str = "metastage";
+/// The version of MetaStage events payloads
+pub const METASTAGE_EVENT_VERSION: &str = "1.0.0";
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EpochPhaseChangeLog {
+    pub epoch: u16,
+    pub phase: EpochPhase,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EpochScheduleLog {
+    pub epoch: u16,
+    #[serde(flatten)]
+    pub schedule: EpochSchedule,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EpochArchiveLog {
+    pub epoch: u16,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CreatorRegistrationLog {
+    pub epoch: u16,
+    pub creator_id: CreatorAccountId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub funding_goal: Option<HashMap<FTAccountId, U128>>,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CreatorMetadataUpdateLog {
+    pub epoch: u16,
+    pub creator_id: CreatorAccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CreatorDeregistrationLog {
+    pub epoch: u16,
+    pub creator_id: CreatorAccountId,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PledgeLog {
+    pub epoch: u16,
+    pub creator_id: CreatorAccountId,
+    pub user_id: UserAccountId,
+    pub ft_token_id: FTAccountId,
+    pub amount: U128,
+    pub nft_rank: UserNFTRank,
+}
+
+/// Logs a pledge of `amount` of `ft_token_id` by `user_id` which was rejected,
+/// and returned to the user, because of `reason`
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PledgeRejectionLog {
+    pub epoch: u16,
+    pub user_id: UserAccountId,
+    pub ft_token_id: FTAccountId,
+    pub amount: U128,
+    pub reason: String,
+}
+
+/// Logs a transfer of `amount` of `ft_token_id`, out of the funds
+/// `user_id` provided to `creator_id`
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct TransferLog {
+    pub epoch: u16,
+    pub creator_id: CreatorAccountId,
+    pub user_id: UserAccountId,
+    pub ft_token_id: FTAccountId,
+    pub amount: U128,
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FeeCollectionLog {
+    pub epoch: u16,
+    pub creator_id: CreatorAccountId,
+    pub ft_token_id: FTAccountId,
+    pub amount: U128,
+}
+
+/// Logs a withdrawal of `amount` of `ft_token_id`, out of the claimable balance of `creator_id`
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CreatorWithdrawalLog {
+    pub creator_id: CreatorAccountId,
+    pub ft_token_id: FTAccountId,
+    pub amount: U128,
+}
+
+/// Logs a withdrawal of `amount` of accrued protocol fees of `ft_token_id`, to `receiver_id`
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+pub struct FeeWithdrawalLog {
+    pub ft_token_id: FTAccountId,
+    pub receiver_id: AccountId,
+    pub amount: U128,
+}
+
+/// [`MetaStageEvent`] enumerates every state transition of the contract,
+/// following the NEP-297 event format
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(tag = "event", content = "data")]
+#[serde(rename_all = "snake_case")]
+pub enum MetaStageEvent {
+    /// Logged on phase transitions. The registration, funding and minting phases of scheduled
+    /// epochs follow the windows of their [`MetaStageEvent::EpochSchedule`], and are not logged
+    EpochPhaseChange(Vec<EpochPhaseChangeLog>),
+    EpochSchedule(Vec<EpochScheduleLog>),
+    EpochArchive(Vec<EpochArchiveLog>),
+    CreatorRegistration(Vec<CreatorRegistrationLog>),
+    CreatorMetadataUpdate(Vec<CreatorMetadataUpdateLog>),
+    CreatorDeregistration(Vec<CreatorDeregistrationLog>),
+    CreatorRegistrationWithdrawal(Vec<CreatorDeregistrationLog>),
+    Pledge(Vec<PledgeLog>),
+    PledgeRejected(Vec<PledgeRejectionLog>),
+    Payout(Vec<TransferLog>),
+    Refund(Vec<TransferLog>),
+    RefundFailed(Vec<TransferLog>),
+    CreatorWithdrawal(Vec<CreatorWithdrawalLog>),
+    CreatorWithdrawalFailed(Vec<CreatorWithdrawalLog>),
+    FeeCollection(Vec<FeeCollectionLog>),
+    FeeWithdrawal(Vec<FeeWithdrawalLog>),
+    FeeWithdrawalFailed(Vec<FeeWithdrawalLog>),
+}
+
+#[derive(Serialize, Debug)]
+#[serde(crate = "near_sdk::serde")]
+struct EventLog<'a> {
+    standard: &'static str,
+    version: &'static str,
+    #[serde(flatten)]
+    event: &'a MetaStageEvent,
+}
+
+impl MetaStageEvent {
+    pub fn to_json_string(&self) -> String {
+        let log = EventLog {
+            standard: METASTAGE_STANDARD_NAME,
+            version: METASTAGE_EVENT_VERSION,
+            event: self,
+        };
+
+        serde_json::to_string(&log)
+            .unwrap_or_else(|_| env::panic_str("MetaStageEvent::failed to serialize event"))
+    }
+
+    pub fn to_json_event_string(&self) -> String {
+        format!("EVENT_JSON:{}", self.to_json_string())
+    }
+
+    /// Logs the event to the host, to be picked up by indexers
+    pub fn emit(self) {
+        env::log_str(&self.to_json_event_string());
+    }
+
+    pub fn epoch_phase_change(epoch: u16, phase: EpochPhase) -> Self {
+        Self::EpochPhaseChange(vec![EpochPhaseChangeLog { epoch, phase }])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_event_serialization_works() {
+        let event = MetaStageEvent::epoch_phase_change(3, EpochPhase::Funding);
+
+        assert_eq!(
+            event.to_json_event_string(),
+            r#"EVENT_JSON:{"standard":"metastage","version":"1.0.0","event":"epoch_phase_change","data":[{"epoch":3,"phase":"Funding"}]}"#
+        );
+
+        let event = MetaStageEvent::Pledge(vec![PledgeLog {
+            epoch: 1,
+            creator_id: "creator.near".to_string().try_into().unwrap(),
+            user_id: "user.near".to_string().try_into().unwrap(),
+            ft_token_id: "wrap.near".to_string().try_into().unwrap(),
+            amount: U128(100),
+            nft_rank: UserNFTRank::Rare,
+        }]);
+
+        assert_eq!(
+            event.to_json_event_string(),
+            r#"EVENT_JSON:{"standard":"metastage","version":"1.0.0","event":"pledge","data":[{"epoch":1,"creator_id":"creator.near","user_id":"user.near","ft_token_id":"wrap.near","amount":"100","nft_rank":"Rare"}]}"#
+        );
+    }
+}
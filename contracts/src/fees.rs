@@ -0,0 +1,214 @@
+use std::collections::HashMap;
+
+use near_sdk::json_types::U128;
+use near_sdk::{env, near_bindgen, AccountId, Promise, PromiseResult};
+
+use crate::{
+    consts::{BASIS_POINTS_DENOMINATOR, GAS_FOR_FT_TRANSFER},
+    error::MetaDaoError,
+    events::FeeWithdrawalLog,
+    *,
+};
+
+/// Splits `amount` into the creator amount and the protocol fee, for a fee of
+/// `fee_bps` basis points. The fee is rounded down, in the creator's favour
+pub fn split_protocol_fee(amount: u128, fee_bps: u16) -> Result<(u128, u128), MetaDaoError> {
+    let protocol_fee = amount
+        .checked_mul(fee_bps as u128)
+        .ok_or(MetaDaoError::ArithmeticOverflow)?
+        / BASIS_POINTS_DENOMINATOR;
+
+    let creator_amount = amount
+        .checked_sub(protocol_fee)
+        .ok_or(MetaDaoError::ArithmeticOverflow)?;
+
+    Ok((creator_amount, protocol_fee))
+}
+
+impl MetaDaoContract {
+    /// Returns the protocol fee, in basis points, of `ft_token_id` in `epoch`, as set when
+    /// the epoch was created
+    pub fn get_protocol_fee(&self, epoch: &Epoch, ft_token_id: &FTAccountId) -> Option<u16> {
+        self.epoch_protocol_fees
+            .get(epoch)
+            .and_then(|fees| fees.get(ft_token_id).copied())
+    }
+
+    /// Adds `amount` of `ft_token_id` to the accrued protocol fees
+    pub fn accrue_protocol_fee(
+        &mut self,
+        ft_token_id: &FTAccountId,
+        amount: u128,
+    ) -> Result<(), MetaDaoError> {
+        let accrued = self.protocol_fees.get(ft_token_id).unwrap_or(0);
+        let accrued = accrued
+            .checked_add(amount)
+            .ok_or(MetaDaoError::ArithmeticOverflow)?;
+
+        self.protocol_fees.insert(ft_token_id, &accrued);
+
+        Ok(())
+    }
+}
+
+#[near_bindgen]
+impl MetaDaoContract {
+    /// Returns the protocol fees accrued, and not yet withdrawn, per fungible token
+    pub fn protocol_fees_accrued(&self) -> HashMap<FTAccountId, U128> {
+        self.protocol_fees
+            .iter()
+            .map(|(ft_token_id, amount)| (ft_token_id, U128(amount)))
+            .collect()
+    }
+
+    /// Transfers `amount` of the accrued protocol fees of `ft_account_id` to `receiver`
+    #[handle_result]
+    pub fn withdraw_protocol_fees(
+        &mut self,
+        ft_account_id: FTAccountId,
+        amount: U128,
+        receiver: AccountId,
+    ) -> Result<Promise, MetaDaoError> {
+        if env::predecessor_account_id() != self.admin {
+            return Err(MetaDaoError::InvalidAdminCall);
+        }
+
+        let accrued = self.protocol_fees.get(&ft_account_id).unwrap_or(0);
+
+        if amount.0 == 0 || amount.0 > accrued {
+            return Err(MetaDaoError::InsufficientProtocolFees);
+        }
+
+        self.protocol_fees
+            .insert(&ft_account_id, &(accrued - amount.0));
+
+        MetaStageEvent::FeeWithdrawal(vec![FeeWithdrawalLog {
+            ft_token_id: ft_account_id.clone(),
+            receiver_id: receiver.clone(),
+            amount,
+        }])
+        .emit();
+
+        Ok(self
+            .internal_transfer(receiver.clone(), ft_account_id.clone(), amount.0)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_FT_TRANSFER)
+                    .on_withdraw_protocol_fees_callback(ft_account_id, amount, receiver),
+            ))
+    }
+
+    /// Restores the withdrawn protocol fees, if the transfer failed
+    #[private]
+    pub fn on_withdraw_protocol_fees_callback(
+        &mut self,
+        ft_account_id: FTAccountId,
+        amount: U128,
+        receiver: AccountId,
+    ) {
+        if env::promise_results_count() != 1 {
+            env::panic_str("MetaDaoContract::withdraw_protocol_fees::Invalid promise result count, one should only have one promise result");
+        }
+
+        if matches!(env::promise_result(0), PromiseResult::Successful(_)) {
+            return;
+        }
+
+        self.accrue_protocol_fee(&ft_account_id, amount.0)
+            .expect("MetaDaoContract::withdraw_protocol_fees::Failed to restore protocol fees");
+
+        MetaStageEvent::FeeWithdrawalFailed(vec![FeeWithdrawalLog {
+            ft_token_id: ft_account_id,
+            receiver_id: receiver,
+            amount,
+        }])
+        .emit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::get_context;
+    use near_sdk::test_utils::accounts;
+    use near_sdk::{testing_env, RuntimeFeesConfig, VMConfig};
+
+    #[test]
+    fn test_split_protocol_fee_works() {
+        assert_eq!(split_protocol_fee(10_000, 500).unwrap(), (9_500, 500));
+        assert_eq!(split_protocol_fee(100, 0).unwrap(), (100, 0));
+        // 1.9999 is rounded down in the creator's favour
+        assert_eq!(split_protocol_fee(19_999, 1).unwrap(), (19_998, 1));
+
+        let one_near = 10u128.pow(24);
+        assert_eq!(
+            split_protocol_fee(one_near + 1, 300).unwrap(),
+            (970 * 10u128.pow(21) + 1, 30 * 10u128.pow(21))
+        );
+
+        assert!(matches!(
+            split_protocol_fee(u128::MAX, 2),
+            Err(MetaDaoError::ArithmeticOverflow)
+        ));
+    }
+
+    #[test]
+    fn test_withdraw_protocol_fees_works() {
+        testing_env!(get_context(accounts(1), 0));
+
+        let mut contract = MetaDaoContract::new(accounts(1));
+        let ft_token_id: FTAccountId = "wrap.near".to_string().try_into().unwrap();
+
+        contract.accrue_protocol_fee(&ft_token_id, 500).unwrap();
+        contract.accrue_protocol_fee(&ft_token_id, 300).unwrap();
+
+        assert_eq!(
+            contract.protocol_fees_accrued(),
+            HashMap::from_iter([(ft_token_id.clone(), U128(800))])
+        );
+
+        assert!(contract
+            .withdraw_protocol_fees(ft_token_id.clone(), U128(801), accounts(3))
+            .is_err());
+
+        assert!(contract
+            .withdraw_protocol_fees(ft_token_id.clone(), U128(600), accounts(3))
+            .is_ok());
+
+        assert_eq!(contract.protocol_fees.get(&ft_token_id), Some(200));
+
+        testing_env!(get_context(accounts(2), 0));
+
+        assert!(matches!(
+            contract.withdraw_protocol_fees(ft_token_id, U128(100), accounts(2)),
+            Err(MetaDaoError::InvalidAdminCall)
+        ));
+    }
+
+    #[test]
+    fn test_on_withdraw_protocol_fees_callback_restores_fees_on_failure() {
+        testing_env!(get_context(accounts(1), 0));
+
+        let mut contract = MetaDaoContract::new(accounts(1));
+        let ft_token_id: FTAccountId = "wrap.near".to_string().try_into().unwrap();
+
+        contract.accrue_protocol_fee(&ft_token_id, 800).unwrap();
+        contract
+            .withdraw_protocol_fees(ft_token_id.clone(), U128(800), accounts(3))
+            .unwrap();
+
+        assert_eq!(contract.protocol_fees.get(&ft_token_id), Some(0));
+
+        testing_env!(
+            get_context(accounts(1), 0),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed]
+        );
+
+        contract.on_withdraw_protocol_fees_callback(ft_token_id.clone(), U128(800), accounts(3));
+
+        assert_eq!(contract.protocol_fees.get(&ft_token_id), Some(800));
+    }
+}
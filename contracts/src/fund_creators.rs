@@ -1,388 +1,616 @@
-use crate::{consts::GAS_FOR_FT_TRANSFER, *};
+use crate::events::CreatorWithdrawalLog;
+use crate::{
+    consts::{
+        FT_STORAGE_DEPOSIT, GAS_FOR_FT_STORAGE_CHECK, GAS_FOR_FT_STORAGE_DEPOSIT,
+        GAS_FOR_FT_TRANSFER, GAS_FOR_WITHDRAW_AFTER_STORAGE_CHECK,
+        GAS_FOR_WITHDRAW_AFTER_STORAGE_DEPOSIT,
+    },
+    *,
+};
 use near_contract_standards::fungible_token::core::ext_ft_core;
+use near_contract_standards::storage_management::StorageBalance;
 use near_sdk::json_types::U128;
-use near_sdk::{env, Promise, PromiseResult};
+use near_sdk::{env, ext_contract, Promise, PromiseError, PromiseResult};
+
+/// The NEP-145 storage management interface of fungible token contracts
+#[allow(dead_code)]
+#[ext_contract(ext_ft_storage)]
+pub trait FungibleTokenStorage {
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance;
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance>;
+}
+
+/// [`CreatorBalance`] is the ledger of a creator net proceeds in a fungible token,
+/// across epochs. The claimable balance is the amount raised, and not yet withdrawn
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, Default, PartialEq)]
+pub struct CreatorBalance {
+    /// Net proceeds credited to the creator on settlement, i.e. after protocol fees
+    pub raised: u128,
+    /// Amount withdrawn by the creator, including withdrawals in flight
+    pub withdrawn: u128,
+}
+
+impl CreatorBalance {
+    pub fn claimable(&self) -> u128 {
+        self.raised - self.withdrawn
+    }
+}
+
+/// JSON view of a [`CreatorBalance`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct CreatorBalanceView {
+    pub raised: U128,
+    pub withdrawn: U128,
+    pub claimable: U128,
+}
+
+impl From<CreatorBalance> for CreatorBalanceView {
+    fn from(balance: CreatorBalance) -> Self {
+        Self {
+            raised: U128(balance.raised),
+            withdrawn: U128(balance.withdrawn),
+            claimable: U128(balance.claimable()),
+        }
+    }
+}
 
 #[near_bindgen]
 impl MetaDaoContract {
-    #[private]
-    pub fn on_external_send_ft_tokens_callback(
+    /// Transfers `amount` of the claimable balance of the caller in `ft_account_id`, to the caller.
+    /// For fungible tokens, the storage registration of the caller is checked first, see
+    /// `on_ft_storage_balance_of_callback`
+    #[handle_result]
+    pub fn withdraw(
         &mut self,
-        creator_account_id: &CreatorAccountId,
-        user_id: &UserAccountId,
-    ) {
-        if env::promise_results_count() != 1 {
-            env::panic_str("MetaDaoContract::external_send_ft_tokens::Invalid promise result count, one should only have one promise result");
-        }
+        ft_account_id: FTAccountId,
+        amount: U128,
+    ) -> Result<Promise, MetaDaoError> {
+        let creator_account_id = env::predecessor_account_id();
+
+        let mut balances = self
+            .creator_balances
+            .get(&creator_account_id)
+            .unwrap_or_default();
 
-        if !matches!(env::promise_result(0), PromiseResult::Successful(_)) {
-            env::panic_str("MetaDaoContract::external_send_ft_tokens::Promise failed");
+        let balance = balances.entry(ft_account_id.clone()).or_default();
+
+        if amount.0 == 0 || amount.0 > balance.claimable() {
+            return Err(MetaDaoError::InsufficientClaimableBalance);
         }
 
-        let mut creator_fundings = self
-            .creator_funding
-            .get(&self.epoch)
-            .ok_or(MetaDaoError::InvalidCurrentEpoch)
-            .expect("MetaDaoContract::external_send_ft_tokens::Invalid current epoch id");
+        balance.withdrawn += amount.0;
+        self.creator_balances.insert(&creator_account_id, &balances);
 
-        let creator_funding = creator_fundings
-            .get(creator_account_id)
-            .ok_or(MetaDaoError::CreatorIsNotRegistered)
-            .expect("MetaDaoContract::external_send_ft_tokens::Creator is not registered");
+        MetaStageEvent::CreatorWithdrawal(vec![CreatorWithdrawalLog {
+            creator_id: creator_account_id.clone(),
+            ft_token_id: ft_account_id.clone(),
+            amount,
+        }])
+        .emit();
 
-        let creator_funding = creator_funding
-            .iter()
-            .map(|ot| {
-                if ot.user_id == *user_id {
-                    ObtainedTokenAmounts {
-                        user_id: ot.user_id.clone(),
-                        already_funded: true,
-                        amount: ot.amount,
-                        nft_rank: ot.nft_rank.clone(),
-                        ft_token_id: ot.ft_token_id.clone(),
-                    }
-                } else {
-                    ot.clone()
-                }
-            })
-            .collect::<Vec<_>>();
+        if ft_account_id == near_token_id() {
+            return Ok(self.internal_withdraw_transfer(creator_account_id, ft_account_id, amount));
+        }
 
-        creator_fundings.insert(creator_account_id, &creator_funding);
-        self.creator_funding.insert(&self.epoch, &creator_fundings);
+        Ok(ext_ft_storage::ext(ft_account_id.clone())
+            .with_static_gas(GAS_FOR_FT_STORAGE_CHECK)
+            .storage_balance_of(creator_account_id.clone())
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_WITHDRAW_AFTER_STORAGE_CHECK)
+                    .on_ft_storage_balance_of_callback(creator_account_id, ft_account_id, amount),
+            ))
     }
-}
 
-#[near_bindgen]
-impl MetaDaoContract {
-    #[payable]
+    /// Transfers the withdrawn `amount` to `creator_account_id`. If the creator has no storage
+    /// on the fungible token contract, a `storage_deposit` is made on its behalf first, funded
+    /// from the fungible token storage reserve of its registration deposit, if it covers it
     #[private]
-    pub fn external_send_ft_tokens(
+    pub fn on_ft_storage_balance_of_callback(
         &mut self,
+        #[callback_result] storage_balance: Result<Option<StorageBalance>, PromiseError>,
         creator_account_id: CreatorAccountId,
-        user_id: &UserAccountId,
         ft_account_id: FTAccountId,
-        amount: u128,
+        amount: U128,
     ) -> Promise {
-        ext_ft_core::ext(ft_account_id)
-            .with_static_gas(GAS_FOR_FT_TRANSFER)
-            .with_attached_deposit(1)
-            .ft_transfer(creator_account_id.clone(), U128(amount), None)
+        // if the storage balance cannot be read, or cannot be funded, the transfer is attempted
+        // anyway, and the withdrawal is restored if it fails
+        if !matches!(storage_balance, Ok(None))
+            || !self.spend_ft_storage_reserve(&creator_account_id)
+        {
+            return self.internal_withdraw_transfer(creator_account_id, ft_account_id, amount);
+        }
+
+        ext_ft_storage::ext(ft_account_id.clone())
+            .with_static_gas(GAS_FOR_FT_STORAGE_DEPOSIT)
+            .with_attached_deposit(FT_STORAGE_DEPOSIT)
+            .storage_deposit(Some(creator_account_id.clone()), Some(true))
             .then(
                 Self::ext(env::current_account_id())
-                    .with_static_gas(GAS_FOR_FT_TRANSFER)
-                    .on_external_send_ft_tokens_callback(&creator_account_id, user_id),
+                    .with_static_gas(GAS_FOR_WITHDRAW_AFTER_STORAGE_DEPOSIT)
+                    .on_ft_storage_deposit_callback(creator_account_id, ft_account_id, amount),
             )
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use crate::tests::get_registry_metadata;
+    /// Transfers the withdrawn `amount` to `creator_account_id`, once its storage is registered on
+    /// the fungible token contract. If the `storage_deposit` failed, its deposit is given back to the
+    /// fungible token storage reserve of the creator, and the transfer is attempted anyway
+    #[private]
+    pub fn on_ft_storage_deposit_callback(
+        &mut self,
+        #[callback_result] storage_deposit: Result<StorageBalance, PromiseError>,
+        creator_account_id: CreatorAccountId,
+        ft_account_id: FTAccountId,
+        amount: U128,
+    ) -> Promise {
+        if storage_deposit.is_err() {
+            let reserve = self
+                .creator_ft_storage_reserves
+                .get(&creator_account_id)
+                .unwrap_or(0);
 
-    use super::*;
-    use near_sdk::{
-        test_utils::{accounts, VMContextBuilder},
-        testing_env, Gas, MockedBlockchain, PromiseResult, RuntimeFeesConfig, VMConfig, VMContext,
-    };
+            self.creator_ft_storage_reserves
+                .insert(&creator_account_id, &(reserve + FT_STORAGE_DEPOSIT));
+        }
 
-    /// utility function for testing callbacks logic
-    #[allow(dead_code)]
-    pub fn testing_env_with_promise_results(
-        context: VMContext,
-        promise_results: Vec<PromiseResult>,
-    ) {
-        near_sdk::env::set_blockchain_interface(MockedBlockchain::new(
-            context,
-            VMConfig::test(),
-            RuntimeFeesConfig::test(),
-            promise_results,
-            Default::default(),
-            Default::default(),
-            None,
-        ));
+        self.internal_withdraw_transfer(creator_account_id, ft_account_id, amount)
     }
 
-    pub fn to_yocto(value: &str) -> u128 {
-        let vals: Vec<_> = value.split('.').collect();
-        let part1 = vals[0].parse::<u128>().unwrap() * 10u128.pow(24);
-        if vals.len() > 1 {
-            let power = vals[1].len() as u32;
-            let part2 = vals[1].parse::<u128>().unwrap() * 10u128.pow(24 - power);
-            part1 + part2
-        } else {
-            part1
+    /// Sends the caller its fungible token storage reserve back, once it is not registered in the
+    /// ongoing epoch and has no fungible tokens left to withdraw. Returns the amount sent back
+    #[handle_result]
+    pub fn withdraw_ft_storage_reserve(&mut self) -> Result<U128, MetaDaoError> {
+        let creator_account_id = env::predecessor_account_id();
+
+        if self.needs_ft_storage_reserve(&creator_account_id) {
+            return Err(MetaDaoError::FTStorageReserveInUse);
         }
-    }
 
-    fn get_context_with_storage(storage: u128) -> VMContext {
-        let contract_account_id: AccountId = "conliq.testnet".to_string().try_into().unwrap();
-
-        VMContextBuilder::new()
-            .current_account_id(contract_account_id)
-            .attached_deposit(to_yocto("1000"))
-            .signer_account_id(accounts(1))
-            .predecessor_account_id(accounts(1))
-            .prepaid_gas(Gas(300 * 10u64.pow(16)))
-            .attached_deposit(storage)
-            .build()
+        Ok(U128(self.release_ft_storage_reserve(&creator_account_id)))
     }
 
-    #[test]
-    fn test_on_external_send_ft_tokens_callback_works() {
-        let admin: AccountId = accounts(1);
-        let storage = parse_near!("0.1 N");
+    /// Restores the withdrawn claimable balance of `creator_account_id`, if the transfer failed,
+    /// e.g. if the creator is not registered on the fungible token contract
+    #[private]
+    pub fn on_withdraw_callback(
+        &mut self,
+        creator_account_id: CreatorAccountId,
+        ft_account_id: FTAccountId,
+        amount: U128,
+    ) {
+        if env::promise_results_count() != 1 {
+            env::panic_str("MetaDaoContract::withdraw::Invalid promise result count, one should only have one promise result");
+        }
 
-        let context = get_context_with_storage(storage);
-        testing_env!(context.clone());
+        if matches!(env::promise_result(0), PromiseResult::Successful(_)) {
+            return;
+        }
 
-        testing_env_with_promise_results(context, vec![PromiseResult::Successful(vec![0u8, 1, 2])]);
+        let mut balances = self
+            .creator_balances
+            .get(&creator_account_id)
+            .expect("MetaDaoContract::withdraw::Creator has no balance");
 
-        let mut contract = MetaDaoContract::new(admin.clone());
+        let balance = balances
+            .get_mut(&ft_account_id)
+            .expect("MetaDaoContract::withdraw::Creator has no balance");
 
-        let mut protocol_accounts_fees = HashMap::<FTAccountId, f64>::new();
+        balance.withdrawn -= amount.0;
+        self.creator_balances.insert(&creator_account_id, &balances);
 
-        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 0.05);
-        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 0.03);
+        MetaStageEvent::CreatorWithdrawalFailed(vec![CreatorWithdrawalLog {
+            creator_id: creator_account_id,
+            ft_token_id: ft_account_id,
+            amount,
+        }])
+        .emit();
+    }
+}
 
-        contract
-            .create_new_epoch(Some(protocol_accounts_fees))
-            .unwrap();
+impl MetaDaoContract {
+    /// Checks if `creator_account_id` may still need its fungible token storage reserve, i.e. if it
+    /// is registered in the ongoing epoch, or has fungible tokens left to withdraw
+    fn needs_ft_storage_reserve(&self, creator_account_id: &CreatorAccountId) -> bool {
+        let is_registered = self.is_epoch_on()
+            && self
+                .creators_per_epoch_set
+                .get(&self.epoch)
+                .map(|creators| creators.contains(creator_account_id))
+                .unwrap_or(false);
+
+        is_registered
+            || self
+                .creator_balances
+                .get(creator_account_id)
+                .map(|balances| {
+                    balances.iter().any(|(ft_account_id, balance)| {
+                        *ft_account_id != near_token_id() && balance.claimable() > 0
+                    })
+                })
+                .unwrap_or(false)
+    }
 
-        contract.set_registration().unwrap();
-        let metadata = get_registry_metadata();
+    /// Sends the fungible token storage reserve of `creator_account_id` back to it, unless it may
+    /// still need it. Returns the amount sent back
+    pub fn release_ft_storage_reserve(&mut self, creator_account_id: &CreatorAccountId) -> Balance {
+        if self.needs_ft_storage_reserve(creator_account_id) {
+            return 0;
+        }
 
-        contract.creator_registration(metadata).unwrap();
+        let reserve = self
+            .creator_ft_storage_reserves
+            .remove(creator_account_id)
+            .unwrap_or(0);
 
-        contract.set_funding().unwrap();
+        if reserve > 0 {
+            Promise::new(creator_account_id.clone()).transfer(reserve);
+        }
 
-        let user_id: AccountId = "user.near".to_string().try_into().unwrap();
-        let creator_account_id: AccountId = accounts(1);
+        reserve
+    }
 
-        let mut creator_fundings = contract.creator_funding.get(&contract.epoch).unwrap();
-        creator_fundings.insert(
-            &creator_account_id,
-            &vec![ObtainedTokenAmounts {
-                user_id: user_id.clone(),
-                amount: 100_u128,
-                nft_rank: UserNFTRank::Common,
-                ft_token_id: "wrap.near".to_string().try_into().unwrap(),
-                already_funded: false,
-            }],
-        );
+    /// Spends a `storage_deposit` out of the fungible token storage reserve of `creator_account_id`,
+    /// if it covers it
+    fn spend_ft_storage_reserve(&mut self, creator_account_id: &CreatorAccountId) -> bool {
+        let reserve = self
+            .creator_ft_storage_reserves
+            .get(creator_account_id)
+            .unwrap_or(0);
 
-        contract
-            .creator_funding
-            .insert(&contract.epoch, &creator_fundings);
+        if reserve < FT_STORAGE_DEPOSIT {
+            return false;
+        }
 
-        contract.on_external_send_ft_tokens_callback(&creator_account_id, &user_id);
+        self.creator_ft_storage_reserves
+            .insert(creator_account_id, &(reserve - FT_STORAGE_DEPOSIT));
 
-        assert_eq!(
-            contract
-                .creator_funding
-                .get(&Epoch(1))
-                .unwrap()
-                .get(&creator_account_id)
-                .unwrap(),
-            vec![ObtainedTokenAmounts {
-                amount: 100_u128,
-                user_id,
-                nft_rank: UserNFTRank::Common,
-                ft_token_id: "wrap.near".to_string().try_into().unwrap(),
-                already_funded: true,
-            }]
-        );
+        true
     }
 
-    #[test]
-    #[should_panic(expected = "MetaDaoContract::external_send_ft_tokens::Promise failed")]
-    fn test_on_external_send_ft_tokens_callback_fails_if_not_successful_promise() {
-        let admin: AccountId = accounts(1);
-        let storage = parse_near!("0.1 N");
-
-        let context = get_context_with_storage(storage);
-        testing_env!(context.clone());
-
-        testing_env_with_promise_results(context, vec![PromiseResult::Failed]);
-
-        let mut contract = MetaDaoContract::new(admin.clone());
+    /// Transfers the withdrawn `amount` of `ft_account_id` to `creator_account_id`,
+    /// restoring its claimable balance if the transfer fails
+    fn internal_withdraw_transfer(
+        &self,
+        creator_account_id: CreatorAccountId,
+        ft_account_id: FTAccountId,
+        amount: U128,
+    ) -> Promise {
+        self.internal_transfer(creator_account_id.clone(), ft_account_id.clone(), amount.0)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_FT_TRANSFER)
+                    .on_withdraw_callback(creator_account_id, ft_account_id, amount),
+            )
+    }
 
-        let mut protocol_accounts_fees = HashMap::<FTAccountId, f64>::new();
+    /// Credits `amount` of `ft_account_id` to the claimable balance of `creator_account_id`
+    pub fn credit_creator(
+        &mut self,
+        creator_account_id: &CreatorAccountId,
+        ft_account_id: &FTAccountId,
+        amount: u128,
+    ) -> Result<(), MetaDaoError> {
+        let mut balances = self
+            .creator_balances
+            .get(creator_account_id)
+            .unwrap_or_default();
 
-        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 0.05);
-        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 0.03);
+        let balance = balances.entry(ft_account_id.clone()).or_default();
+        balance.raised = balance
+            .raised
+            .checked_add(amount)
+            .ok_or(MetaDaoError::ArithmeticOverflow)?;
 
-        contract
-            .create_new_epoch(Some(protocol_accounts_fees))
-            .unwrap();
+        self.creator_balances.insert(creator_account_id, &balances);
 
-        contract.set_registration().unwrap();
-        let metadata = get_registry_metadata();
+        Ok(())
+    }
 
-        contract.creator_registration(metadata).unwrap();
+    /// Transfers `amount` of `ft_account_id` to `receiver_id`, either as native NEAR,
+    /// for the NEAR sentinel token id, or via a NEP-141 `ft_transfer` otherwise
+    pub fn internal_transfer(
+        &self,
+        receiver_id: AccountId,
+        ft_account_id: FTAccountId,
+        amount: u128,
+    ) -> Promise {
+        if ft_account_id == near_token_id() {
+            return Promise::new(receiver_id).transfer(amount);
+        }
 
-        contract.set_funding().unwrap();
+        ext_ft_core::ext(ft_account_id)
+            .with_static_gas(GAS_FOR_FT_TRANSFER)
+            .with_attached_deposit(1)
+            .ft_transfer(receiver_id, U128(amount), None)
+    }
+}
 
-        let user_id: AccountId = "user.near".to_string().try_into().unwrap();
-        let creator_account_id: AccountId = accounts(1);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{get_context, testing_env_with_promise_results};
+    use near_sdk::{
+        mock::VmAction,
+        test_utils::{accounts, get_created_receipts, VMContextBuilder},
+        testing_env, PromiseResult, RuntimeFeesConfig, VMConfig,
+    };
 
-        contract.on_external_send_ft_tokens_callback(&creator_account_id, &user_id);
+    fn creator_balance(contract: &MetaDaoContract, ft_token_id: &str) -> CreatorBalance {
+        contract
+            .creator_balances
+            .get(&accounts(1))
+            .unwrap()
+            .get(&ft_token_id.parse().unwrap())
+            .cloned()
+            .unwrap()
     }
 
     #[test]
-    #[should_panic(expected = "MetaDaoContract::external_send_ft_tokens::Promise failed")]
-    fn test_on_external_send_ft_tokens_callback_fails_if_not_successful_promise2() {
-        let admin: AccountId = accounts(1);
-        let storage = parse_near!("0.1 N");
+    fn test_withdraw_works() {
+        let context = get_context(accounts(1), 0);
+        testing_env!(context);
 
-        let context = get_context_with_storage(storage);
-        testing_env!(context.clone());
+        let mut contract = MetaDaoContract::new(accounts(1));
+        let ft_token_id: FTAccountId = "wrap.near".to_string().try_into().unwrap();
 
-        testing_env_with_promise_results(context, vec![PromiseResult::NotReady]);
+        contract
+            .credit_creator(&accounts(1), &ft_token_id, 95)
+            .unwrap();
+        contract
+            .credit_creator(&accounts(1), &ft_token_id, 95)
+            .unwrap();
 
-        let mut contract = MetaDaoContract::new(admin.clone());
+        assert!(contract.withdraw(ft_token_id.clone(), U128(100)).is_ok());
 
-        let mut protocol_accounts_fees = HashMap::<FTAccountId, f64>::new();
+        assert_eq!(
+            creator_balance(&contract, "wrap.near"),
+            CreatorBalance {
+                raised: 190,
+                withdrawn: 100
+            }
+        );
+        assert_eq!(
+            contract.creator_balances(accounts(1)),
+            HashMap::from([(
+                ft_token_id,
+                CreatorBalanceView {
+                    raised: U128(190),
+                    withdrawn: U128(100),
+                    claimable: U128(90),
+                }
+            )])
+        );
+    }
 
-        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 0.05);
-        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 0.03);
+    #[test]
+    fn test_withdraw_fails_if_claimable_balance_is_insufficient() {
+        let context = get_context(accounts(1), 0);
+        testing_env!(context);
+
+        let mut contract = MetaDaoContract::new(accounts(1));
+        let ft_token_id: FTAccountId = "wrap.near".to_string().try_into().unwrap();
 
         contract
-            .create_new_epoch(Some(protocol_accounts_fees))
+            .credit_creator(&accounts(1), &ft_token_id, 190)
             .unwrap();
 
-        contract.set_registration().unwrap();
-        let metadata = get_registry_metadata();
-
-        contract.creator_registration(metadata).unwrap();
-
-        contract.set_funding().unwrap();
+        for (ft_token_id, amount) in [("wrap.near", 0), ("wrap.near", 191), ("usn", 1)] {
+            assert!(matches!(
+                contract.withdraw(ft_token_id.parse().unwrap(), U128(amount)),
+                Err(MetaDaoError::InsufficientClaimableBalance)
+            ));
+        }
 
-        let user_id: AccountId = "user.near".to_string().try_into().unwrap();
-        let creator_account_id: AccountId = accounts(1);
+        // only the creator can withdraw its balance
+        testing_env!(VMContextBuilder::new()
+            .predecessor_account_id(accounts(2))
+            .build());
 
-        contract.on_external_send_ft_tokens_callback(&creator_account_id, &user_id);
+        assert!(matches!(
+            contract.withdraw(ft_token_id, U128(100)),
+            Err(MetaDaoError::InsufficientClaimableBalance)
+        ));
+        assert_eq!(creator_balance(&contract, "wrap.near").claimable(), 190);
     }
 
     #[test]
-    #[should_panic(
-        expected = "MetaDaoContract::external_send_ft_tokens::Invalid promise result count, one should only have one promise result"
-    )]
-    fn test_on_external_send_ft_tokens_callback_fails_if_more_than_one_promise() {
-        let admin: AccountId = accounts(1);
-        let storage = parse_near!("0.1 N");
+    fn test_withdraw_ft_storage_reserve_once_nothing_is_left_to_withdraw() {
+        testing_env!(get_context(accounts(1), 0));
 
-        let context = get_context_with_storage(storage);
-        testing_env!(context.clone());
-
-        testing_env_with_promise_results(
-            context,
-            vec![
-                PromiseResult::Successful(vec![0_u8, 1, 2]),
-                PromiseResult::Successful(vec![0_u8, 1]),
-            ],
-        );
-
-        let mut contract = MetaDaoContract::new(admin.clone());
-
-        let mut protocol_accounts_fees = HashMap::<FTAccountId, f64>::new();
-
-        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 0.05);
-        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 0.03);
+        let mut contract = MetaDaoContract::new(accounts(1));
+        let ft_token_id: FTAccountId = "wrap.near".to_string().try_into().unwrap();
 
         contract
-            .create_new_epoch(Some(protocol_accounts_fees))
+            .creator_ft_storage_reserves
+            .insert(&accounts(1), &FT_STORAGE_DEPOSIT);
+        contract
+            .credit_creator(&accounts(1), &ft_token_id, 190)
             .unwrap();
 
-        contract.set_registration().unwrap();
-        let metadata = get_registry_metadata();
-
-        contract.creator_registration(metadata).unwrap();
-
-        contract.set_funding().unwrap();
+        assert!(matches!(
+            contract.withdraw_ft_storage_reserve(),
+            Err(MetaDaoError::FTStorageReserveInUse)
+        ));
 
-        let user_id: AccountId = "user.near".to_string().try_into().unwrap();
-        let creator_account_id: AccountId = accounts(1);
+        contract.withdraw(ft_token_id, U128(190)).unwrap();
 
-        contract.on_external_send_ft_tokens_callback(&creator_account_id, &user_id);
+        testing_env!(get_context(accounts(1), 0));
+        assert_eq!(
+            contract.withdraw_ft_storage_reserve().unwrap(),
+            U128(FT_STORAGE_DEPOSIT)
+        );
+        assert!(contract
+            .creator_ft_storage_reserves
+            .get(&accounts(1))
+            .is_none());
+
+        let transfers: Vec<_> = get_created_receipts()
+            .into_iter()
+            .filter(|receipt| receipt.receiver_id == accounts(1))
+            .flat_map(|receipt| receipt.actions)
+            .collect();
+        assert_eq!(
+            transfers,
+            vec![VmAction::Transfer {
+                deposit: FT_STORAGE_DEPOSIT
+            }]
+        );
     }
 
     #[test]
-    #[should_panic(expected = "MetaDaoContract::external_send_ft_tokens::Invalid current epoch id")]
-    fn test_on_external_send_ft_tokens_callback_fails_if_epoch_is_invalid() {
-        let admin: AccountId = accounts(1);
-        let storage = parse_near!("0.1 N");
-
-        let context = get_context_with_storage(storage);
+    fn test_on_withdraw_callback_works() {
+        let context = get_context(accounts(1), 0);
         testing_env!(context.clone());
 
-        testing_env_with_promise_results(
+        let mut contract = MetaDaoContract::new(accounts(1));
+        let ft_token_id: FTAccountId = "wrap.near".to_string().try_into().unwrap();
+
+        contract
+            .credit_creator(&accounts(1), &ft_token_id, 190)
+            .unwrap();
+        contract.withdraw(ft_token_id.clone(), U128(190)).unwrap();
+
+        testing_env!(
             context,
-            vec![PromiseResult::Successful(vec![0_u8, 1, 2])],
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])]
         );
 
-        let mut contract = MetaDaoContract::new(admin.clone());
+        contract.on_withdraw_callback(accounts(1), ft_token_id, U128(190));
 
-        let mut protocol_accounts_fees = HashMap::<FTAccountId, f64>::new();
+        assert_eq!(creator_balance(&contract, "wrap.near").claimable(), 0);
+    }
+
+    #[test]
+    fn test_on_withdraw_callback_restores_balance_if_not_successful_promise() {
+        let context = get_context(accounts(1), 0);
+        testing_env!(context.clone());
 
-        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 0.05);
-        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 0.03);
+        let mut contract = MetaDaoContract::new(accounts(1));
+        let ft_token_id: FTAccountId = "wrap.near".to_string().try_into().unwrap();
 
         contract
-            .create_new_epoch(Some(protocol_accounts_fees))
+            .credit_creator(&accounts(1), &ft_token_id, 190)
             .unwrap();
 
-        contract.set_registration().unwrap();
-        let metadata = get_registry_metadata();
+        for promise_result in [PromiseResult::Failed, PromiseResult::NotReady] {
+            testing_env!(context.clone());
+            contract.withdraw(ft_token_id.clone(), U128(150)).unwrap();
+
+            testing_env!(
+                context.clone(),
+                VMConfig::test(),
+                RuntimeFeesConfig::test(),
+                Default::default(),
+                vec![promise_result]
+            );
+
+            contract.on_withdraw_callback(accounts(1), ft_token_id.clone(), U128(150));
+
+            assert_eq!(
+                creator_balance(&contract, "wrap.near"),
+                CreatorBalance {
+                    raised: 190,
+                    withdrawn: 0
+                }
+            );
+        }
+    }
 
-        contract.creator_registration(metadata).unwrap();
+    #[test]
+    fn test_on_ft_storage_balance_of_callback_registers_creator_storage() {
+        let context = get_context(accounts(1), 0);
+        testing_env!(context.clone());
 
-        contract.set_funding().unwrap();
+        let mut contract = MetaDaoContract::new(accounts(1));
+        let ft_token_id: FTAccountId = "wrap.near".to_string().try_into().unwrap();
 
-        let user_id: AccountId = "user.near".to_string().try_into().unwrap();
-        let creator_account_id: AccountId = accounts(1);
+        contract
+            .creator_ft_storage_reserves
+            .insert(&accounts(1), &(FT_STORAGE_DEPOSIT + 1));
+        let reserve =
+            |contract: &MetaDaoContract| contract.creator_ft_storage_reserves.get(&accounts(1));
+
+        // creators already registered on the fungible token contract are paid right away
+        contract.on_ft_storage_balance_of_callback(
+            Ok(Some(StorageBalance {
+                total: U128(FT_STORAGE_DEPOSIT),
+                available: U128(0),
+            })),
+            accounts(1),
+            ft_token_id.clone(),
+            U128(100),
+        );
+        assert_eq!(reserve(&contract), Some(FT_STORAGE_DEPOSIT + 1));
 
-        contract.epoch = Epoch(0);
+        contract.on_ft_storage_balance_of_callback(
+            Ok(None),
+            accounts(1),
+            ft_token_id.clone(),
+            U128(100),
+        );
+        assert_eq!(reserve(&contract), Some(1));
+
+        // the transfer is still attempted if the reserve does not cover the registration
+        contract.on_ft_storage_balance_of_callback(
+            Ok(None),
+            accounts(1),
+            ft_token_id.clone(),
+            U128(100),
+        );
+        assert_eq!(reserve(&contract), Some(1));
 
-        contract.on_external_send_ft_tokens_callback(&creator_account_id, &user_id);
+        let wrap_near_receipts = get_created_receipts()
+            .iter()
+            .filter(|receipt| receipt.receiver_id.as_str() == "wrap.near")
+            .count();
+
+        // two transfers, and a single storage deposit, followed by the transfer
+        assert_eq!(wrap_near_receipts, 3);
+
+        // a failed storage deposit gives its deposit back to the reserve
+        testing_env!(context);
+        contract.on_ft_storage_deposit_callback(
+            Err(PromiseError::Failed),
+            accounts(1),
+            ft_token_id,
+            U128(100),
+        );
+        assert_eq!(reserve(&contract), Some(FT_STORAGE_DEPOSIT + 1));
     }
 
     #[test]
     #[should_panic(
-        expected = "MetaDaoContract::external_send_ft_tokens::Creator is not registered"
+        expected = "MetaDaoContract::withdraw::Invalid promise result count, one should only have one promise result"
     )]
-    fn test_on_external_send_ft_tokens_callback_fails_if_creator_is_not_registered() {
+    fn test_on_withdraw_callback_fails_if_more_than_one_promise() {
         let admin: AccountId = accounts(1);
         let storage = parse_near!("0.1 N");
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(1), storage);
         testing_env!(context.clone());
 
         testing_env_with_promise_results(
             context,
-            vec![PromiseResult::Successful(vec![0_u8, 1, 2])],
+            vec![
+                PromiseResult::Successful(vec![0_u8, 1, 2]),
+                PromiseResult::Successful(vec![0_u8, 1]),
+            ],
         );
 
-        let mut contract = MetaDaoContract::new(admin.clone());
-
-        let mut protocol_accounts_fees = HashMap::<FTAccountId, f64>::new();
+        let mut contract = MetaDaoContract::new(admin);
 
-        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 0.05);
-        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 0.03);
-
-        contract
-            .create_new_epoch(Some(protocol_accounts_fees))
-            .unwrap();
-
-        contract.set_registration().unwrap();
-        let metadata = get_registry_metadata();
-
-        contract.creator_registration(metadata).unwrap();
-
-        contract.set_funding().unwrap();
-
-        let user_id: AccountId = "user.near".to_string().try_into().unwrap();
-        let creator_account_id: AccountId = "creator.near".to_string().try_into().unwrap();
-
-        contract.on_external_send_ft_tokens_callback(&creator_account_id, &user_id);
+        contract.on_withdraw_callback(
+            accounts(1),
+            "wrap.near".to_string().try_into().unwrap(),
+            U128(95),
+        );
     }
 }
@@ -1,21 +1,48 @@
 use std::collections::HashMap;
 
+use fund_creators::CreatorBalance;
+use migration::LegacyObtainedTokenAmounts;
 use near_contract_standards::non_fungible_token::metadata::NFTContractMetadata;
-use near_contract_standards::non_fungible_token::NonFungibleToken;
+use near_contract_standards::non_fungible_token::{NonFungibleToken, TokenId};
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
-use near_sdk::collections::{LazyOption, UnorderedMap, UnorderedSet};
+use near_sdk::collections::{LazyOption, UnorderedMap, UnorderedSet, Vector};
+use near_sdk::json_types::U128;
 use near_sdk::serde::{Deserialize, Serialize};
-use near_sdk::{env, near_bindgen, AccountId, BorshStorageKey, PanicOnDefault};
+use near_sdk::{
+    env, near_bindgen, AccountId, Balance, BorshStorageKey, PanicOnDefault, StorageUsage,
+};
+#[cfg(test)]
 use near_units::parse_near;
-use registry::CreatorMetadata;
-
-use crate::{error::MetaDaoError, nft::UserNFTRank};
+use phase::EpochPhase;
+use provenance::NFTProvenance;
+use registry::{CreatorMetadata, LegacyCreatorMetadata};
+use schedule::EpochSchedule;
+use settlement::PayoutStatus;
+use storage::AccountStorage;
+
+use crate::{
+    consts::{DEFAULT_MAX_CREATOR_METADATA_SIZE, MAX_PROTOCOL_FEE_BPS},
+    error::MetaDaoError,
+    events::{EpochScheduleLog, MetaStageEvent, PledgeLog},
+    nft::UserNFTRank,
+};
 
 mod consts;
 mod error;
+mod events;
+mod fees;
 mod fund_creators;
+mod migration;
 mod nft;
+mod phase;
+mod pledge;
+mod provenance;
+mod refunds;
 mod registry;
+mod schedule;
+mod settlement;
+mod storage;
+#[cfg(test)]
 mod tests;
 mod token_receiver;
 mod views;
@@ -24,6 +51,13 @@ pub type CreatorAccountId = AccountId;
 pub type UserAccountId = AccountId;
 pub type FTAccountId = AccountId;
 
+/// Returns the sentinel [`FTAccountId`] of native NEAR
+pub fn near_token_id() -> FTAccountId {
+    consts::NEAR_TOKEN_ID
+        .parse()
+        .expect("MetaDaoContract::near_token_id: invalid NEAR token id")
+}
+
 #[derive(
     BorshDeserialize, BorshSerialize, Deserialize, Serialize, Debug, Clone, Copy, Eq, PartialEq,
 )]
@@ -65,7 +99,12 @@ pub struct ObtainedTokenAmounts {
     pub ft_token_id: FTAccountId,
     pub amount: u128,
     pub nft_rank: UserNFTRank,
-    pub already_funded: bool,
+    /// The edition of the NFT within its tier, assigned in pledge order, starting at 1
+    pub edition: u64,
+    pub payout_status: PayoutStatus,
+    /// Whether the NFT was minted to the backer, either on settlement or claimed via `claim_nft`
+    pub minted: bool,
+    pub already_refunded: bool,
 }
 
 /// [`StorageKey`] provides a suitable interface to deal with
@@ -95,16 +134,53 @@ pub struct MetaDaoContract {
     pub creators_per_epoch_set: UnorderedMap<Epoch, UnorderedSet<CreatorAccountId>>,
     /// Container for each Creator NFT metadata, per epoch
     pub creators_metadata: UnorderedMap<Epoch, UnorderedMap<CreatorAccountId, CreatorMetadata>>,
-    /// Tracks if epoch is on
-    pub is_epoch_on: bool,
-    /// Tracks if contract is in registration period
-    pub in_registration: bool,
-    /// Tracks if contract is in funding period
-    pub in_funding: bool,
-    /// Tracks if contract is in minting period
-    pub in_minting: bool,
-    /// MetaDao protocol fee
-    pub protocol_allowed_tokens_fees: UnorderedMap<FTAccountId, f64>,
+    /// Container for each Creator optional funding goal, per fungible token, per epoch
+    pub creators_funding_goals:
+        UnorderedMap<Epoch, UnorderedMap<CreatorAccountId, HashMap<FTAccountId, u128>>>,
+    /// Container for Creators whose backers are allowed to claim refunds, per epoch
+    pub refundable_creators: UnorderedMap<Epoch, UnorderedSet<CreatorAccountId>>,
+    /// Container for the optional time based schedule of registration, funding and minting, per epoch
+    pub epoch_schedules: UnorderedMap<Epoch, EpochSchedule>,
+    /// Tracks the phase of each epoch
+    pub epoch_phases: UnorderedMap<Epoch, EpochPhase>,
+    /// MetaDao protocol fee, in basis points, per allowed fungible token
+    pub protocol_allowed_tokens_fees: UnorderedMap<FTAccountId, u16>,
+    /// Protocol fees accrued on creators payouts, and not yet withdrawn, per fungible token
+    pub protocol_fees: UnorderedMap<FTAccountId, u128>,
+    /// The protocol fees, in basis points, per allowed fungible token, each epoch was created with
+    pub epoch_protocol_fees: UnorderedMap<Epoch, HashMap<FTAccountId, u16>>,
+    /// Container for each Creator balance of net proceeds, to be withdrawn, per fungible token
+    pub creator_balances: UnorderedMap<CreatorAccountId, HashMap<FTAccountId, CreatorBalance>>,
+    /// Container for the NEAR reserved out of each creator registration deposit, to register
+    /// its storage on fungible token contracts when it withdraws
+    pub creator_ft_storage_reserves: UnorderedMap<CreatorAccountId, Balance>,
+    /// Container for each account NEP-145 storage balance
+    pub storage_accounts: UnorderedMap<AccountId, AccountStorage>,
+    /// The bytes used by the storage balance entry of an account
+    pub account_storage_usage: StorageUsage,
+    /// Container for the bytes charged to each account, per epoch, released once the epoch is archived
+    pub epoch_storage_usage: UnorderedMap<Epoch, UnorderedMap<AccountId, StorageUsage>>,
+    /// The number of refund transfers awaiting their callback, per epoch, which keep it from being archived
+    pub pending_refunds: UnorderedMap<Epoch, u64>,
+    /// The maximum size, in bytes, of the metadata of a creator registration
+    pub max_creator_metadata_size: StorageUsage,
+    /// Container for the copies pledged for, per creator and NFT rank, per epoch
+    pub tier_sold_copies:
+        UnorderedMap<Epoch, UnorderedMap<CreatorAccountId, HashMap<UserNFTRank, u64>>>,
+    /// Container for the provenance of each minted NFT
+    pub nft_provenance: UnorderedMap<TokenId, NFTProvenance>,
+    /// Container for the NFTs minted for the backers of each creator, in mint order, per epoch
+    pub creator_tokens: UnorderedMap<Epoch, UnorderedMap<CreatorAccountId, Vector<TokenId>>>,
+    /// The account allowed, besides the admin and the creators, to settle creators
+    pub settlement_operator: Option<AccountId>,
+    /// Container for the metadata of the creators registered before the upgrade, in the legacy
+    /// layout, per epoch, until migrated via `migrate_legacy_creators`
+    pub legacy_creators_metadata:
+        UnorderedMap<Epoch, UnorderedMap<CreatorAccountId, LegacyCreatorMetadata>>,
+    /// Container for the funding entries of the creators registered before the upgrade, in the
+    /// legacy layout, per epoch, until migrated via `migrate_legacy_creators`
+    pub legacy_creator_funding:
+        UnorderedMap<Epoch, UnorderedMap<CreatorAccountId, Vec<LegacyObtainedTokenAmounts>>>,
     /// A Non Fungible Token interface
     pub tokens: NonFungibleToken,
     /// A Non Fungible Token interface for Metadata
@@ -134,7 +210,60 @@ impl MetaDaoContract {
             UnorderedMap<CreatorAccountId, CreatorMetadata>,
         >::new(b"h".to_vec());
 
-        let protocol_allowed_tokens_fees = UnorderedMap::<FTAccountId, f64>::new(b"g".to_vec());
+        let creators_funding_goals = UnorderedMap::<
+            Epoch,
+            UnorderedMap<CreatorAccountId, HashMap<FTAccountId, u128>>,
+        >::new(b"k".to_vec());
+
+        let refundable_creators =
+            UnorderedMap::<Epoch, UnorderedSet<CreatorAccountId>>::new(b"i".to_vec());
+
+        let epoch_schedules = UnorderedMap::<Epoch, EpochSchedule>::new(b"l".to_vec());
+
+        let epoch_phases = UnorderedMap::<Epoch, EpochPhase>::new(b"m".to_vec());
+
+        let protocol_allowed_tokens_fees = UnorderedMap::<FTAccountId, u16>::new(b"g".to_vec());
+
+        let protocol_fees = UnorderedMap::<FTAccountId, u128>::new(b"n".to_vec());
+
+        let epoch_protocol_fees =
+            UnorderedMap::<Epoch, HashMap<FTAccountId, u16>>::new(b"y".to_vec());
+
+        let creator_balances =
+            UnorderedMap::<CreatorAccountId, HashMap<FTAccountId, CreatorBalance>>::new(
+                b"o".to_vec(),
+            );
+
+        let creator_ft_storage_reserves =
+            UnorderedMap::<CreatorAccountId, Balance>::new(b"v".to_vec());
+
+        let storage_accounts = UnorderedMap::<AccountId, AccountStorage>::new(b"q".to_vec());
+
+        let epoch_storage_usage =
+            UnorderedMap::<Epoch, UnorderedMap<AccountId, StorageUsage>>::new(b"r".to_vec());
+
+        let tier_sold_copies = UnorderedMap::<
+            Epoch,
+            UnorderedMap<CreatorAccountId, HashMap<UserNFTRank, u64>>,
+        >::new(b"s".to_vec());
+        let pending_refunds = UnorderedMap::<Epoch, u64>::new(b"z".to_vec());
+
+        let nft_provenance = UnorderedMap::<TokenId, NFTProvenance>::new(b"t".to_vec());
+
+        let creator_tokens =
+            UnorderedMap::<Epoch, UnorderedMap<CreatorAccountId, Vector<TokenId>>>::new(
+                b"u".to_vec(),
+            );
+
+        let legacy_creators_metadata = UnorderedMap::<
+            Epoch,
+            UnorderedMap<CreatorAccountId, LegacyCreatorMetadata>,
+        >::new(b"w".to_vec());
+
+        let legacy_creator_funding = UnorderedMap::<
+            Epoch,
+            UnorderedMap<CreatorAccountId, Vec<LegacyObtainedTokenAmounts>>,
+        >::new(b"x".to_vec());
 
         let tokens = NonFungibleToken::new(
             StorageKey::NonFungibleToken,
@@ -157,22 +286,41 @@ impl MetaDaoContract {
             }),
         );
 
-        Self {
+        let mut this = Self {
             admin,
             epoch: Epoch(0u16),
             creator_funding,
             user_funds,
-            is_epoch_on: false,
-            in_registration: false,
-            in_funding: false,
-            in_minting: false,
+            epoch_phases,
             creators_per_epoch_set,
             creators_metadata,
+            creators_funding_goals,
+            refundable_creators,
+            epoch_schedules,
             protocol_allowed_tokens_fees,
+            protocol_fees,
+            epoch_protocol_fees,
+            creator_balances,
+            creator_ft_storage_reserves,
+            storage_accounts,
+            account_storage_usage: 0,
+            epoch_storage_usage,
+            pending_refunds,
+            max_creator_metadata_size: DEFAULT_MAX_CREATOR_METADATA_SIZE,
+            tier_sold_copies,
+            nft_provenance,
+            creator_tokens,
+            settlement_operator: None,
+            legacy_creators_metadata,
+            legacy_creator_funding,
             tokens,
             metadata,
             nft_id: 0u32,
-        }
+        };
+
+        this.measure_account_storage_usage();
+
+        this
     }
 
     #[handle_result]
@@ -181,22 +329,13 @@ impl MetaDaoContract {
             return Err(MetaDaoError::InvalidAdminCall);
         }
 
-        if !self.is_epoch_on {
+        if !self.is_epoch_on() {
             return Err(MetaDaoError::EpochIsOff);
         }
 
-        if self.in_funding {
-            return Err(MetaDaoError::AlreadyInFunding);
-        }
-
-        if !self.in_registration {
-            return Err(MetaDaoError::NotInRegistrationPeriod);
-        }
+        self.assert_unscheduled_epoch()?;
 
-        self.in_registration = false;
-        self.in_funding = true;
-
-        Ok(())
+        self.transition_epoch_phase(EpochPhase::Funding)
     }
 
     #[handle_result]
@@ -205,35 +344,55 @@ impl MetaDaoContract {
             return Err(MetaDaoError::InvalidAdminCall);
         }
 
-        if !self.is_epoch_on {
+        if !self.is_epoch_on() {
             return Err(MetaDaoError::EpochIsOff);
         }
 
-        if self.in_funding {
-            return Err(MetaDaoError::AlreadyInFunding);
+        self.assert_unscheduled_epoch()?;
+
+        self.transition_epoch_phase(EpochPhase::Registration)
+    }
+
+    #[handle_result]
+    pub fn set_minting(&mut self) -> Result<(), MetaDaoError> {
+        if env::predecessor_account_id() != self.admin {
+            return Err(MetaDaoError::InvalidAdminCall);
         }
 
-        if self.in_registration {
-            return Err(MetaDaoError::AlreadyInRegistration);
+        if !self.is_epoch_on() {
+            return Err(MetaDaoError::EpochIsOff);
         }
 
-        self.in_registration = true;
+        self.assert_unscheduled_epoch()?;
 
-        Ok(())
+        self.transition_epoch_phase(EpochPhase::Minting)
     }
 
     #[handle_result]
     pub fn create_new_epoch(
         &mut self,
-        protocol_tokens_fees: Option<HashMap<FTAccountId, f64>>,
+        protocol_tokens_fees: Option<HashMap<FTAccountId, u16>>,
+        schedule: Option<EpochSchedule>,
     ) -> Result<(), MetaDaoError> {
         if env::predecessor_account_id() != self.admin {
             return Err(MetaDaoError::InvalidAdminCall);
         }
 
-        // it is enough to check this, as if epoch is set to false
-        // Registration and funding should also be set to false
-        if self.is_epoch_on {
+        if let Some(schedule) = &schedule {
+            schedule.validate()?;
+        }
+
+        if let Some(ft_acc_ids_fees) = &protocol_tokens_fees {
+            if ft_acc_ids_fees
+                .values()
+                .any(|fee| *fee > MAX_PROTOCOL_FEE_BPS)
+            {
+                return Err(MetaDaoError::InvalidProtocolFee);
+            }
+        }
+
+        // a new epoch can only start once the previous one is settled or cancelled
+        if self.is_epoch_on() {
             return Err(MetaDaoError::UnableToCreateNewEpoch);
         }
 
@@ -273,7 +432,47 @@ impl MetaDaoContract {
                     .to_vec(),
             ),
         );
-        let mut protocol_allowed_tokens_fees = UnorderedMap::<FTAccountId, f64>::new(
+        self.creators_funding_goals.insert(
+            &self.epoch,
+            &UnorderedMap::<CreatorAccountId, HashMap<FTAccountId, u128>>::new(
+                format!("creator funding goals for epoch: {}", self.epoch.count())
+                    .as_bytes()
+                    .to_vec(),
+            ),
+        );
+        self.refundable_creators.insert(
+            &self.epoch,
+            &UnorderedSet::<CreatorAccountId>::new(
+                format!("refundable creators for epoch: {}", self.epoch.count())
+                    .as_bytes()
+                    .to_vec(),
+            ),
+        );
+        self.epoch_storage_usage.insert(
+            &self.epoch,
+            &UnorderedMap::<AccountId, StorageUsage>::new(
+                format!("storage usage for epoch: {}", self.epoch.count())
+                    .as_bytes()
+                    .to_vec(),
+            ),
+        );
+        self.tier_sold_copies.insert(
+            &self.epoch,
+            &UnorderedMap::<CreatorAccountId, HashMap<UserNFTRank, u64>>::new(
+                format!("tier sold copies for epoch: {}", self.epoch.count())
+                    .as_bytes()
+                    .to_vec(),
+            ),
+        );
+        self.creator_tokens.insert(
+            &self.epoch,
+            &UnorderedMap::<CreatorAccountId, Vector<TokenId>>::new(
+                format!("creator tokens for epoch: {}", self.epoch.count())
+                    .as_bytes()
+                    .to_vec(),
+            ),
+        );
+        let mut protocol_allowed_tokens_fees = UnorderedMap::<FTAccountId, u16>::new(
             format!("allowed_ft_acc_ids for epoch: {}", self.epoch.count())
                 .as_bytes()
                 .to_vec(),
@@ -287,7 +486,25 @@ impl MetaDaoContract {
             self.protocol_allowed_tokens_fees = protocol_allowed_tokens_fees;
         }
 
-        self.is_epoch_on = true;
+        // past epochs may be settled after this one started, with the fees they were created with
+        self.epoch_protocol_fees.insert(
+            &self.epoch,
+            &self.protocol_allowed_tokens_fees.iter().collect(),
+        );
+
+        self.epoch_phases.insert(&self.epoch, &EpochPhase::Idle);
+
+        MetaStageEvent::epoch_phase_change(self.epoch.count(), EpochPhase::Idle).emit();
+
+        if let Some(schedule) = schedule {
+            self.epoch_schedules.insert(&self.epoch, &schedule);
+
+            MetaStageEvent::EpochSchedule(vec![EpochScheduleLog {
+                epoch: self.epoch.count(),
+                schedule,
+            }])
+            .emit();
+        }
 
         Ok(())
     }
@@ -298,23 +515,11 @@ impl MetaDaoContract {
             return Err(MetaDaoError::InvalidAdminCall);
         }
 
-        if !self.is_epoch_on {
+        if !self.is_epoch_on() {
             return Err(MetaDaoError::EpochIsOff);
         }
 
-        if self.in_registration {
-            return Err(MetaDaoError::AlreadyInRegistration);
-        }
-
-        if self.in_funding {
-            return Err(MetaDaoError::AlreadyInFunding);
-        }
-
-        self.is_epoch_on = false;
-        self.in_funding = false;
-        self.in_registration = false;
-
-        Ok(())
+        self.transition_epoch_phase(EpochPhase::Settled)
     }
 
     #[payable]
@@ -328,22 +533,59 @@ impl MetaDaoContract {
         amount: u128,
         ft_token_id: FTAccountId,
     ) -> Result<(), MetaDaoError> {
-        if env::attached_deposit() < parse_near!("0.01 N") {
-            return Err(MetaDaoError::UserDidNotAttachEnoughFunds);
-        }
-
-        if !self.is_epoch_on {
+        if !self.is_epoch_on() {
             return Err(MetaDaoError::EpochIsOff);
         }
 
-        if !self.in_funding {
+        if !self.is_in_funding() {
             return Err(MetaDaoError::NotInFundingPeriod);
         }
 
-        if self.creators_per_epoch_set.get(&self.epoch).is_none() {
+        let creators_per_epoch = self
+            .creators_per_epoch_set
+            .get(&self.epoch)
+            .ok_or(MetaDaoError::InvalidCurrentEpoch)?;
+
+        if !creators_per_epoch.contains(&creator_account_id) {
             return Err(MetaDaoError::CreatorIsNotRegistered);
         }
 
+        // each funding entry takes one copy of its tier, until the tier is sold out
+        let available_copies = self
+            .get_tier_availability(&self.epoch, &creator_account_id)?
+            .get(&nft_rank)
+            .copied()
+            .unwrap_or(0);
+
+        if available_copies == 0 {
+            return Err(MetaDaoError::TierSoldOut);
+        }
+
+        let mut tier_sold_copies = self
+            .tier_sold_copies
+            .get(&self.epoch)
+            .ok_or(MetaDaoError::InvalidCurrentEpoch)?;
+
+        let mut sold_copies = tier_sold_copies
+            .get(&creator_account_id)
+            .unwrap_or_default();
+        let edition = sold_copies.entry(nft_rank.clone()).or_insert(0);
+        *edition += 1;
+        let edition = *edition;
+
+        tier_sold_copies.insert(&creator_account_id, &sold_copies);
+        self.tier_sold_copies.insert(&self.epoch, &tier_sold_copies);
+
+        MetaStageEvent::Pledge(vec![PledgeLog {
+            epoch: self.epoch.count(),
+            creator_id: creator_account_id.clone(),
+            user_id: user_id.clone(),
+            ft_token_id: ft_token_id.clone(),
+            amount: U128(amount),
+            nft_rank: nft_rank.clone(),
+        }])
+        .emit();
+
         let funded_token_amount = FundedTokenAmount {
             creator_id: creator_account_id.clone(),
             ft_token_id: ft_token_id.clone(),
@@ -372,7 +614,10 @@ impl MetaDaoContract {
             ft_token_id,
             amount,
             nft_rank,
-            already_funded: false,
+            edition,
+            payout_status: PayoutStatus::Pending,
+            minted: false,
+            already_refunded: false,
         };
 
         let mut creator_fundings = self
@@ -400,55 +645,11 @@ impl MetaDaoContract {
 #[cfg(test)]
 mod test {
     use super::*;
-    use near_sdk::test_utils::{accounts, VMContextBuilder};
-    use near_sdk::{
-        testing_env, AccountId, Gas, MockedBlockchain, PromiseResult, RuntimeFeesConfig, VMConfig,
-        VMContext,
-    };
+    use crate::tests::{get_allowed_registry_metadata, get_context};
+    use near_sdk::test_utils::accounts;
+    use near_sdk::{testing_env, AccountId};
     use std::convert::TryInto;
 
-    /// utility function for testing callbacks logic
-    #[allow(dead_code)]
-    pub fn testing_env_with_promise_results(
-        context: VMContext,
-        promise_results: Vec<PromiseResult>,
-    ) {
-        near_sdk::env::set_blockchain_interface(MockedBlockchain::new(
-            context,
-            VMConfig::test(),
-            RuntimeFeesConfig::test(),
-            promise_results,
-            Default::default(),
-            Default::default(),
-            None,
-        ));
-    }
-
-    pub fn to_yocto(value: &str) -> u128 {
-        let vals: Vec<_> = value.split('.').collect();
-        let part1 = vals[0].parse::<u128>().unwrap() * 10u128.pow(24);
-        if vals.len() > 1 {
-            let power = vals[1].len() as u32;
-            let part2 = vals[1].parse::<u128>().unwrap() * 10u128.pow(24 - power);
-            part1 + part2
-        } else {
-            part1
-        }
-    }
-
-    fn get_context_with_storage(storage: u128) -> VMContext {
-        let contract_account_id: AccountId = "conliq.testnet".to_string().try_into().unwrap();
-
-        VMContextBuilder::new()
-            .current_account_id(contract_account_id)
-            .attached_deposit(to_yocto("1000"))
-            .signer_account_id(accounts(1))
-            .predecessor_account_id(accounts(1))
-            .prepaid_gas(Gas(300 * 10u64.pow(16)))
-            .attached_deposit(storage)
-            .build()
-    }
-
     #[test]
     fn test_new_works() {
         let admin: AccountId = "admin.near".to_string().try_into().unwrap();
@@ -456,10 +657,8 @@ mod test {
 
         assert_eq!(contract.epoch, Epoch(0u16));
 
-        assert!(!contract.is_epoch_on);
-        assert!(!contract.in_registration);
-        assert!(!contract.in_funding);
-        assert!(!contract.in_minting);
+        assert!(!contract.is_epoch_on());
+        assert!(contract.epoch_phases.is_empty());
 
         assert_eq!(contract.admin, admin);
         assert!(contract.creator_funding.is_empty());
@@ -477,19 +676,20 @@ mod test {
         let admin: AccountId = accounts(1);
         let storage = 1u128;
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(1), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        contract.is_epoch_on = true;
-        contract.in_registration = true;
+        contract
+            .epoch_phases
+            .insert(&contract.epoch, &EpochPhase::Registration);
 
         contract.set_funding().unwrap();
 
-        assert!(contract.in_funding);
-        assert!(!contract.in_registration);
-        assert!(contract.is_epoch_on);
+        assert!(contract.is_in_funding());
+        assert!(!contract.is_in_registration());
+        assert!(contract.is_epoch_on());
     }
 
     #[test]
@@ -497,13 +697,14 @@ mod test {
         let admin: AccountId = "admin.near".to_string().try_into().unwrap();
         let storage = 1u128;
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(1), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        contract.is_epoch_on = true;
-        contract.in_registration = true;
+        contract
+            .epoch_phases
+            .insert(&contract.epoch, &EpochPhase::Registration);
 
         assert!(contract
             .set_funding()
@@ -517,13 +718,14 @@ mod test {
         let admin = accounts(1);
         let storage = 1u128;
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(1), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        contract.is_epoch_on = false;
-        contract.in_registration = true;
+        contract
+            .epoch_phases
+            .insert(&contract.epoch, &EpochPhase::Settled);
 
         assert!(contract
             .set_funding()
@@ -537,13 +739,14 @@ mod test {
         let admin = accounts(1);
         let storage = 1u128;
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(1), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        contract.is_epoch_on = true;
-        contract.in_registration = false;
+        contract
+            .epoch_phases
+            .insert(&contract.epoch, &EpochPhase::Idle);
 
         assert!(contract
             .set_funding()
@@ -557,14 +760,14 @@ mod test {
         let admin = accounts(1);
         let storage = 1u128;
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(1), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        contract.is_epoch_on = true;
-        contract.in_registration = true;
-        contract.in_funding = true;
+        contract
+            .epoch_phases
+            .insert(&contract.epoch, &EpochPhase::Funding);
 
         assert!(contract
             .set_funding()
@@ -578,18 +781,20 @@ mod test {
         let admin = accounts(1);
         let storage = 1u128;
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(1), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        contract.is_epoch_on = true;
+        contract
+            .epoch_phases
+            .insert(&contract.epoch, &EpochPhase::Idle);
 
         contract.set_registration().unwrap();
 
-        assert!(contract.in_registration);
-        assert!(contract.is_epoch_on);
-        assert!(!contract.in_funding);
+        assert!(contract.is_in_registration());
+        assert!(contract.is_epoch_on());
+        assert!(!contract.is_in_funding());
     }
 
     #[test]
@@ -597,13 +802,14 @@ mod test {
         let admin: AccountId = "admin.near".to_string().try_into().unwrap();
         let storage = 1u128;
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(1), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        contract.is_epoch_on = true;
-        contract.in_registration = true;
+        contract
+            .epoch_phases
+            .insert(&contract.epoch, &EpochPhase::Registration);
 
         assert!(contract
             .set_registration()
@@ -617,13 +823,14 @@ mod test {
         let admin = accounts(1);
         let storage = 1u128;
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(1), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        contract.is_epoch_on = false;
-        contract.in_registration = true;
+        contract
+            .epoch_phases
+            .insert(&contract.epoch, &EpochPhase::Settled);
 
         assert!(contract
             .set_registration()
@@ -637,14 +844,14 @@ mod test {
         let admin = accounts(1);
         let storage = 1u128;
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(1), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        contract.is_epoch_on = true;
-        contract.in_registration = true;
-        contract.in_funding = false;
+        contract
+            .epoch_phases
+            .insert(&contract.epoch, &EpochPhase::Registration);
 
         assert!(contract
             .set_registration()
@@ -658,14 +865,14 @@ mod test {
         let admin = accounts(1);
         let storage = 1u128;
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(1), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        contract.is_epoch_on = true;
-        contract.in_registration = true;
-        contract.in_funding = true;
+        contract
+            .epoch_phases
+            .insert(&contract.epoch, &EpochPhase::Funding);
 
         assert!(contract
             .set_registration()
@@ -674,23 +881,69 @@ mod test {
             .contains("Already in funding period"));
     }
 
+    #[test]
+    fn test_set_minting_works() {
+        let admin = accounts(1);
+        let storage = 1u128;
+
+        let context = get_context(accounts(1), storage);
+        testing_env!(context);
+
+        let mut contract = MetaDaoContract::new(admin.clone());
+
+        contract
+            .epoch_phases
+            .insert(&contract.epoch, &EpochPhase::Funding);
+
+        contract.set_minting().unwrap();
+
+        assert!(contract.is_in_minting());
+        assert!(!contract.is_in_funding());
+        assert!(contract.is_epoch_on());
+    }
+
+    #[test]
+    fn test_set_minting_fails_if_not_in_funding() {
+        let admin = accounts(1);
+        let storage = 1u128;
+
+        let context = get_context(accounts(1), storage);
+        testing_env!(context);
+
+        let mut contract = MetaDaoContract::new(admin.clone());
+
+        contract
+            .epoch_phases
+            .insert(&contract.epoch, &EpochPhase::Registration);
+
+        assert!(contract
+            .set_minting()
+            .unwrap_err()
+            .to_string()
+            .contains("Not in funding period"));
+    }
+
     #[test]
     fn test_end_epoch_works() {
         let admin = accounts(1);
         let storage = 1u128;
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(1), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        contract.is_epoch_on = true;
+        contract
+            .epoch_phases
+            .insert(&contract.epoch, &EpochPhase::Minting);
 
         contract.end_epoch().unwrap();
 
-        assert!(!contract.is_epoch_on);
-        assert!(!contract.in_funding);
-        assert!(!contract.in_registration);
+        assert!(!contract.is_epoch_on());
+        assert_eq!(
+            contract.get_epoch_phase(contract.epoch),
+            Some(EpochPhase::Settled)
+        );
     }
 
     #[test]
@@ -698,7 +951,7 @@ mod test {
         let admin: AccountId = "admin.near".to_string().try_into().unwrap();
         let storage = 1u128;
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(1), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
@@ -715,12 +968,14 @@ mod test {
         let admin: AccountId = accounts(1);
         let storage = 1u128;
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(1), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        contract.is_epoch_on = false;
+        contract
+            .epoch_phases
+            .insert(&contract.epoch, &EpochPhase::Settled);
 
         assert!(contract
             .end_epoch()
@@ -734,13 +989,14 @@ mod test {
         let admin: AccountId = accounts(1);
         let storage = 1u128;
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(1), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        contract.is_epoch_on = true;
-        contract.in_registration = true;
+        contract
+            .epoch_phases
+            .insert(&contract.epoch, &EpochPhase::Registration);
 
         assert!(contract
             .end_epoch()
@@ -754,14 +1010,14 @@ mod test {
         let admin: AccountId = accounts(1);
         let storage = 1u128;
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(1), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        contract.is_epoch_on = true;
-        contract.in_registration = false;
-        contract.in_funding = true;
+        contract
+            .epoch_phases
+            .insert(&contract.epoch, &EpochPhase::Funding);
 
         assert!(contract
             .end_epoch()
@@ -775,22 +1031,26 @@ mod test {
         let admin: AccountId = accounts(1);
         let storage = 1u128;
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(1), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        let mut ft_acc_ids_fees = HashMap::<FTAccountId, f64>::new();
+        let mut ft_acc_ids_fees = HashMap::<FTAccountId, u16>::new();
 
-        ft_acc_ids_fees.insert("wrap.near".to_string().try_into().unwrap(), 0.05);
-        ft_acc_ids_fees.insert("usn".to_string().try_into().unwrap(), 0.03);
+        ft_acc_ids_fees.insert("wrap.near".to_string().try_into().unwrap(), 500);
+        ft_acc_ids_fees.insert("usn".to_string().try_into().unwrap(), 300);
 
-        contract.create_new_epoch(Some(ft_acc_ids_fees)).unwrap();
+        contract
+            .create_new_epoch(Some(ft_acc_ids_fees), None)
+            .unwrap();
 
         assert_eq!(contract.epoch, Epoch(1u16));
-        assert!(contract.is_epoch_on);
-        assert!(!contract.in_funding);
-        assert!(!contract.in_registration);
+        assert!(contract.is_epoch_on());
+        assert_eq!(
+            contract.get_epoch_phase(contract.epoch),
+            Some(EpochPhase::Idle)
+        );
 
         let epoch = Epoch(1u16);
 
@@ -809,14 +1069,36 @@ mod test {
                 .protocol_allowed_tokens_fees
                 .get(&AccountId::try_from(String::from("wrap.near")).unwrap())
                 .unwrap(),
-            0.05_f64
+            500_u16
         );
         assert_eq!(
             contract
                 .protocol_allowed_tokens_fees
                 .get(&AccountId::try_from(String::from("usn")).unwrap())
                 .unwrap(),
-            0.03_f64
+            300_u16
+        );
+    }
+
+    #[test]
+    fn test_epoch_phase_changes_emit_events() {
+        let admin: AccountId = accounts(1);
+        let storage = 1u128;
+
+        let context = get_context(accounts(1), storage);
+        testing_env!(context);
+
+        let mut contract = MetaDaoContract::new(admin.clone());
+
+        contract.create_new_epoch(None, None).unwrap();
+        contract.set_registration().unwrap();
+
+        assert_eq!(
+            near_sdk::test_utils::get_logs(),
+            vec![
+                r#"EVENT_JSON:{"standard":"metastage","version":"1.0.0","event":"epoch_phase_change","data":[{"epoch":1,"phase":"Idle"}]}"#,
+                r#"EVENT_JSON:{"standard":"metastage","version":"1.0.0","event":"epoch_phase_change","data":[{"epoch":1,"phase":"Registration"}]}"#,
+            ]
         );
     }
 
@@ -825,18 +1107,18 @@ mod test {
         let admin: AccountId = "admin.near".to_string().try_into().unwrap();
         let storage = 1u128;
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(1), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        let mut protocol_accounts_fees = HashMap::<FTAccountId, f64>::new();
+        let mut protocol_accounts_fees = HashMap::<FTAccountId, u16>::new();
 
-        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 0.05);
-        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 0.03);
+        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 500);
+        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 300);
 
         contract
-            .create_new_epoch(Some(protocol_accounts_fees))
+            .create_new_epoch(Some(protocol_accounts_fees), None)
             .unwrap_err()
             .to_string()
             .contains("Invalid Admin call");
@@ -847,42 +1129,70 @@ mod test {
         let admin: AccountId = accounts(1);
         let storage = 1u128;
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(1), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        contract.is_epoch_on = true;
+        contract
+            .epoch_phases
+            .insert(&contract.epoch, &EpochPhase::Idle);
 
-        let mut protocol_accounts_fees = HashMap::<FTAccountId, f64>::new();
+        let mut protocol_accounts_fees = HashMap::<FTAccountId, u16>::new();
 
-        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 0.05);
-        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 0.03);
+        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 500);
+        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 300);
 
         contract
-            .create_new_epoch(Some(protocol_accounts_fees))
+            .create_new_epoch(Some(protocol_accounts_fees), None)
             .unwrap_err()
             .to_string()
             .contains("Unable to create a new epoch, while previous epoch is still ongoing");
     }
 
+    #[test]
+    fn test_create_new_epoch_fails_if_protocol_fee_above_max() {
+        let admin: AccountId = accounts(1);
+        let storage = 1u128;
+
+        let context = get_context(accounts(1), storage);
+        testing_env!(context);
+
+        let mut contract = MetaDaoContract::new(admin.clone());
+
+        let mut protocol_accounts_fees = HashMap::<FTAccountId, u16>::new();
+
+        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 500);
+        protocol_accounts_fees.insert(
+            "usn".to_string().try_into().unwrap(),
+            MAX_PROTOCOL_FEE_BPS + 1,
+        );
+
+        assert!(contract
+            .create_new_epoch(Some(protocol_accounts_fees), None)
+            .unwrap_err()
+            .to_string()
+            .contains("Protocol fee exceeds the maximum allowed fee"));
+        assert_eq!(contract.get_epoch_phase(Epoch(1)), None);
+    }
+
     #[test]
     fn test_user_funding_creator_works() {
         let admin: AccountId = accounts(1);
         let storage = parse_near!("0.1 N");
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(1), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        let mut protocol_accounts_fees = HashMap::<FTAccountId, f64>::new();
+        let mut protocol_accounts_fees = HashMap::<FTAccountId, u16>::new();
 
-        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 0.05);
-        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 0.03);
+        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 500);
+        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 300);
 
         contract
-            .create_new_epoch(Some(protocol_accounts_fees))
+            .create_new_epoch(Some(protocol_accounts_fees), None)
             .unwrap();
 
         contract.set_registration().unwrap();
@@ -893,11 +1203,9 @@ mod test {
         let ft_token_id: AccountId = "wrap.near".to_string().try_into().unwrap();
         let amount = 100_u128;
 
-        use crate::tests::get_registry_metadata;
-
-        let metadata = get_registry_metadata();
+        let metadata = get_allowed_registry_metadata(&contract);
 
-        contract.creator_registration(metadata).unwrap();
+        contract.creator_registration(metadata, None).unwrap();
 
         contract.set_funding().unwrap();
 
@@ -920,8 +1228,11 @@ mod test {
             ObtainedTokenAmounts {
                 user_id,
                 amount,
-                already_funded: false,
+                payout_status: PayoutStatus::Pending,
+                minted: false,
+                already_refunded: false,
                 nft_rank: UserNFTRank::Common,
+                edition: 1,
                 ft_token_id
             }
         );
@@ -951,8 +1262,11 @@ mod test {
             ObtainedTokenAmounts {
                 user_id,
                 amount,
-                already_funded: false,
+                payout_status: PayoutStatus::Pending,
+                minted: false,
+                already_refunded: false,
                 nft_rank: UserNFTRank::Uncommon,
+                edition: 1,
                 ft_token_id
             }
         );
@@ -982,8 +1296,11 @@ mod test {
             ObtainedTokenAmounts {
                 user_id,
                 amount,
-                already_funded: false,
+                payout_status: PayoutStatus::Pending,
+                minted: false,
+                already_refunded: false,
                 nft_rank: UserNFTRank::Rare,
+                edition: 1,
                 ft_token_id
             }
         );
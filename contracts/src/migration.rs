@@ -0,0 +1,555 @@
+use std::collections::HashMap;
+use std::convert::TryFrom;
+
+use near_contract_standards::non_fungible_token::metadata::NFTContractMetadata;
+use near_contract_standards::non_fungible_token::{NonFungibleToken, TokenId};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::{LazyOption, UnorderedMap, UnorderedSet, Vector};
+use near_sdk::{env, near_bindgen, AccountId, Balance, StorageUsage};
+
+use crate::{
+    consts::{BASIS_POINTS_DENOMINATOR, DEFAULT_MAX_CREATOR_METADATA_SIZE},
+    error::MetaDaoError,
+    registry::{CreatorMetadata, LegacyCreatorMetadata},
+    *,
+};
+
+/// The layout of [`ObtainedTokenAmounts`] before editions, payouts, claims and refunds were tracked
+/// per entry. Entries `already_funded` had their NFT minted, and their funds sent to the creator
+#[derive(BorshDeserialize, BorshSerialize, Clone, Debug, PartialEq)]
+pub struct LegacyObtainedTokenAmounts {
+    pub user_id: UserAccountId,
+    pub ft_token_id: FTAccountId,
+    pub amount: u128,
+    pub nft_rank: UserNFTRank,
+    pub already_funded: bool,
+}
+
+/// The layout of [`MetaDaoContract`] before epoch phases, i.e. the version 0 of the contract state
+#[derive(BorshDeserialize, BorshSerialize)]
+pub struct MetaDaoContractV0 {
+    pub admin: AccountId,
+    pub epoch: Epoch,
+    pub creator_funding:
+        UnorderedMap<Epoch, UnorderedMap<CreatorAccountId, Vec<LegacyObtainedTokenAmounts>>>,
+    pub user_funds: UnorderedMap<Epoch, UnorderedMap<UserAccountId, Vec<FundedTokenAmount>>>,
+    pub creators_per_epoch_set: UnorderedMap<Epoch, UnorderedSet<CreatorAccountId>>,
+    pub creators_metadata:
+        UnorderedMap<Epoch, UnorderedMap<CreatorAccountId, LegacyCreatorMetadata>>,
+    pub is_epoch_on: bool,
+    pub in_registration: bool,
+    pub in_funding: bool,
+    pub in_minting: bool,
+    /// Protocol fees, as a fraction of the funded amount
+    pub protocol_allowed_tokens_fees: UnorderedMap<FTAccountId, f64>,
+    pub tokens: NonFungibleToken,
+    pub metadata: LazyOption<NFTContractMetadata>,
+    pub nft_id: u32,
+}
+
+/// A collection of values per creator, per epoch
+type PerEpochCreators<V> = UnorderedMap<Epoch, UnorderedMap<CreatorAccountId, V>>;
+
+/// Moves the per-epoch collections of `legacy`, stored at `prefix`, to a new map at `legacy_prefix`,
+/// and replaces them at `prefix` with empty collections of the current layout, so that the legacy
+/// and the migrated creators of an epoch never share a collection
+fn split_legacy_epochs<L, T>(
+    mut legacy: PerEpochCreators<L>,
+    prefix: &[u8],
+    legacy_prefix: &[u8],
+    collection_name: &str,
+) -> (PerEpochCreators<T>, PerEpochCreators<L>)
+where
+    L: BorshSerialize + BorshDeserialize,
+    T: BorshSerialize + BorshDeserialize,
+{
+    let legacy_epochs = legacy.to_vec();
+    legacy.clear();
+
+    let mut current = UnorderedMap::new(prefix.to_vec());
+    let mut moved = UnorderedMap::new(legacy_prefix.to_vec());
+
+    for (epoch, legacy_collection) in legacy_epochs {
+        moved.insert(&epoch, &legacy_collection);
+        current.insert(
+            &epoch,
+            &UnorderedMap::new(
+                format!("migrated {} for epoch: {}", collection_name, epoch.count())
+                    .as_bytes()
+                    .to_vec(),
+            ),
+        );
+    }
+
+    (current, moved)
+}
+
+impl MetaDaoContract {
+    /// Converts the legacy funding entries of a creator, numbering the editions of each
+    /// tier in pledge order. Entries `already_funded` are credited, the others are left pending
+    fn migrate_funding_entries(
+        entries: Vec<LegacyObtainedTokenAmounts>,
+    ) -> Vec<ObtainedTokenAmounts> {
+        let mut editions = HashMap::<UserNFTRank, u64>::new();
+
+        entries
+            .into_iter()
+            .map(|entry| {
+                let edition = editions.entry(entry.nft_rank.clone()).or_insert(0);
+                *edition += 1;
+
+                ObtainedTokenAmounts {
+                    user_id: entry.user_id,
+                    ft_token_id: entry.ft_token_id,
+                    amount: entry.amount,
+                    nft_rank: entry.nft_rank,
+                    edition: *edition,
+                    payout_status: if entry.already_funded {
+                        PayoutStatus::Credited
+                    } else {
+                        PayoutStatus::Pending
+                    },
+                    minted: entry.already_funded,
+                    already_refunded: false,
+                }
+            })
+            .collect()
+    }
+}
+
+#[near_bindgen]
+impl MetaDaoContract {
+    /// Migrates the version 0 state of the contract to the current layout. It has to be called,
+    /// along with the upgrade, while no epoch is on: past epochs become settled, and protocol fees
+    /// are converted to basis points. The creators of past epochs are kept in legacy collections,
+    /// until rewritten via `migrate_legacy_creators`, and cannot be archived until then
+    #[init(ignore_state)]
+    #[private]
+    pub fn migrate() -> Self {
+        let mut old: MetaDaoContractV0 = env::state_read()
+            .unwrap_or_else(|| env::panic_str("MetaDaoContract::migrate::No state to migrate"));
+
+        if old.is_epoch_on || old.in_registration || old.in_funding || old.in_minting {
+            env::panic_str("MetaDaoContract::migrate::An epoch is on, it has to be ended first");
+        }
+
+        let mut epoch_phases = UnorderedMap::<Epoch, EpochPhase>::new(b"m".to_vec());
+        for count in 1..=old.epoch.count() {
+            epoch_phases.insert(&Epoch(count), &EpochPhase::Settled);
+        }
+
+        // fees are read before clearing the legacy map, which may share the prefix of the new one
+        let legacy_fees = old.protocol_allowed_tokens_fees.to_vec();
+        old.protocol_allowed_tokens_fees.clear();
+
+        let mut protocol_allowed_tokens_fees = UnorderedMap::<FTAccountId, u16>::new(b"g".to_vec());
+        for (ft_token_id, fee) in legacy_fees {
+            protocol_allowed_tokens_fees.insert(
+                &ft_token_id,
+                &((fee * BASIS_POINTS_DENOMINATOR as f64).round() as u16),
+            );
+        }
+
+        let (creators_metadata, legacy_creators_metadata) =
+            split_legacy_epochs(old.creators_metadata, b"h", b"w", "creators metadata");
+        let (creator_funding, legacy_creator_funding) =
+            split_legacy_epochs(old.creator_funding, b"a", b"x", "creator funding");
+
+        let mut creator_tokens = UnorderedMap::<
+            Epoch,
+            UnorderedMap<CreatorAccountId, Vector<TokenId>>,
+        >::new(b"u".to_vec());
+        let mut refundable_creators =
+            UnorderedMap::<Epoch, UnorderedSet<CreatorAccountId>>::new(b"i".to_vec());
+        let mut epoch_protocol_fees =
+            UnorderedMap::<Epoch, HashMap<FTAccountId, u16>>::new(b"y".to_vec());
+        let migrated_fees: HashMap<FTAccountId, u16> =
+            protocol_allowed_tokens_fees.iter().collect();
+
+        for count in 1..=old.epoch.count() {
+            creator_tokens.insert(
+                &Epoch(count),
+                &UnorderedMap::new(
+                    format!("creator tokens for epoch: {}", count)
+                        .as_bytes()
+                        .to_vec(),
+                ),
+            );
+            refundable_creators.insert(
+                &Epoch(count),
+                &UnorderedSet::new(
+                    format!("refundable creators for epoch: {}", count)
+                        .as_bytes()
+                        .to_vec(),
+                ),
+            );
+            // the legacy fees were not recorded per epoch, so past epochs settle with the last ones
+            epoch_protocol_fees.insert(&Epoch(count), &migrated_fees);
+        }
+
+        let mut this = Self {
+            admin: old.admin,
+            epoch: old.epoch,
+            creator_funding,
+            user_funds: old.user_funds,
+            creators_per_epoch_set: old.creators_per_epoch_set,
+            creators_metadata,
+            creators_funding_goals: UnorderedMap::new(b"k".to_vec()),
+            refundable_creators,
+            epoch_schedules: UnorderedMap::new(b"l".to_vec()),
+            epoch_phases,
+            protocol_allowed_tokens_fees,
+            protocol_fees: UnorderedMap::new(b"n".to_vec()),
+            epoch_protocol_fees,
+            creator_balances: UnorderedMap::new(b"o".to_vec()),
+            storage_accounts: UnorderedMap::new(b"q".to_vec()),
+            account_storage_usage: 0,
+            epoch_storage_usage: UnorderedMap::<Epoch, UnorderedMap<AccountId, StorageUsage>>::new(
+                b"r".to_vec(),
+            ),
+            pending_refunds: UnorderedMap::new(b"z".to_vec()),
+            max_creator_metadata_size: DEFAULT_MAX_CREATOR_METADATA_SIZE,
+            tier_sold_copies: UnorderedMap::new(b"s".to_vec()),
+            nft_provenance: UnorderedMap::new(b"t".to_vec()),
+            creator_tokens,
+            settlement_operator: None,
+            creator_ft_storage_reserves: UnorderedMap::<CreatorAccountId, Balance>::new(
+                b"v".to_vec(),
+            ),
+            legacy_creators_metadata,
+            legacy_creator_funding,
+            tokens: old.tokens,
+            metadata: old.metadata,
+            nft_id: old.nft_id,
+        };
+
+        this.measure_account_storage_usage();
+
+        this
+    }
+
+    /// Rewrites up to `limit` creators of `epoch`, registered before the upgrade, from the legacy
+    /// layout: their metadata is grouped into [`registry::TierConfig`] records, and their funding
+    /// entries numbered by edition. Entries which were not funded yet are left pending, to be
+    /// settled via `settle_creator` on `epoch`, or refunded if their creator has no metadata
+    /// to mint their NFTs from. Returns the number of migrated creators
+    #[handle_result]
+    pub fn migrate_legacy_creators(
+        &mut self,
+        epoch: Epoch,
+        limit: Option<u64>,
+    ) -> Result<u64, MetaDaoError> {
+        if env::predecessor_account_id() != self.admin {
+            return Err(MetaDaoError::InvalidAdminCall);
+        }
+
+        let mut legacy_creators_metadata = self
+            .legacy_creators_metadata
+            .get(&epoch)
+            .ok_or(MetaDaoError::NoLegacyCreatorsToMigrate)?;
+        let mut legacy_creator_funding = self
+            .legacy_creator_funding
+            .get(&epoch)
+            .ok_or(MetaDaoError::NoLegacyCreatorsToMigrate)?;
+
+        let mut creators_metadata = self
+            .creators_metadata
+            .get(&epoch)
+            .ok_or(MetaDaoError::InvalidCurrentEpoch)?;
+        let mut creator_fundings = self
+            .creator_funding
+            .get(&epoch)
+            .ok_or(MetaDaoError::InvalidCurrentEpoch)?;
+        let mut refundable_creators = self
+            .refundable_creators
+            .get(&epoch)
+            .ok_or(MetaDaoError::InvalidCurrentEpoch)?;
+
+        let limit = limit.unwrap_or(u64::MAX) as usize;
+
+        // creators which were funded without being registered only have funding entries
+        let mut creator_account_ids = legacy_creators_metadata
+            .keys()
+            .take(limit)
+            .collect::<Vec<_>>();
+        creator_account_ids.extend(
+            legacy_creator_funding
+                .keys()
+                .filter(|creator_account_id| {
+                    legacy_creators_metadata.get(creator_account_id).is_none()
+                })
+                .take(limit - creator_account_ids.len()),
+        );
+
+        for creator_account_id in &creator_account_ids {
+            if let Some(legacy_metadata) = legacy_creators_metadata.remove(creator_account_id) {
+                creators_metadata.insert(
+                    creator_account_id,
+                    &CreatorMetadata::try_from(legacy_metadata)?,
+                );
+            }
+
+            if let Some(legacy_entries) = legacy_creator_funding.remove(creator_account_id) {
+                if creators_metadata.get(creator_account_id).is_none() {
+                    refundable_creators.insert(creator_account_id);
+                }
+
+                creator_fundings.insert(
+                    creator_account_id,
+                    &Self::migrate_funding_entries(legacy_entries),
+                );
+            }
+        }
+
+        self.creators_metadata.insert(&epoch, &creators_metadata);
+        self.creator_funding.insert(&epoch, &creator_fundings);
+        self.refundable_creators
+            .insert(&epoch, &refundable_creators);
+
+        if legacy_creators_metadata.is_empty() && legacy_creator_funding.is_empty() {
+            self.legacy_creators_metadata.remove(&epoch);
+            self.legacy_creator_funding.remove(&epoch);
+        } else {
+            self.legacy_creators_metadata
+                .insert(&epoch, &legacy_creators_metadata);
+            self.legacy_creator_funding
+                .insert(&epoch, &legacy_creator_funding);
+        }
+
+        Ok(creator_account_ids.len() as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{get_context, get_legacy_registry_metadata};
+    use near_sdk::test_utils::accounts;
+    use near_sdk::{testing_env, PromiseResult, RuntimeFeesConfig, VMConfig};
+
+    fn legacy_entry(
+        user: &str,
+        nft_rank: UserNFTRank,
+        already_funded: bool,
+    ) -> LegacyObtainedTokenAmounts {
+        LegacyObtainedTokenAmounts {
+            user_id: user.parse().unwrap(),
+            ft_token_id: "wrap.near".parse().unwrap(),
+            amount: 100,
+            nft_rank,
+            already_funded,
+        }
+    }
+
+    /// Writes the version 0 state, after a first epoch which was ended
+    fn write_v0_state() {
+        let epoch = Epoch(1);
+
+        let mut creators_metadata = UnorderedMap::new(
+            format!("creator nft rankings for epoch: {}", epoch.count())
+                .as_bytes()
+                .to_vec(),
+        );
+        creators_metadata.insert(&accounts(1), &get_legacy_registry_metadata());
+        creators_metadata.insert(&accounts(2), &get_legacy_registry_metadata());
+
+        let mut creator_funding = UnorderedMap::new(
+            format!("creator_funding for epoch: {}", epoch.count())
+                .as_bytes()
+                .to_vec(),
+        );
+        creator_funding.insert(
+            &accounts(1),
+            &vec![
+                legacy_entry("user.near", UserNFTRank::Common, true),
+                legacy_entry("user.near", UserNFTRank::Rare, false),
+                legacy_entry("other_user.near", UserNFTRank::Common, false),
+            ],
+        );
+        // funded without being registered
+        creator_funding.insert(
+            &accounts(3),
+            &vec![legacy_entry("user.near", UserNFTRank::Common, false)],
+        );
+
+        let mut v0_creators_metadata = UnorderedMap::new(b"h".to_vec());
+        v0_creators_metadata.insert(&epoch, &creators_metadata);
+        let mut v0_creator_funding = UnorderedMap::new(b"a".to_vec());
+        v0_creator_funding.insert(&epoch, &creator_funding);
+
+        let mut protocol_allowed_tokens_fees = UnorderedMap::new(
+            format!("allowed_ft_acc_ids for epoch: {}", epoch.count())
+                .as_bytes()
+                .to_vec(),
+        );
+        protocol_allowed_tokens_fees.insert(&"wrap.near".parse().unwrap(), &0.05);
+
+        let contract = MetaDaoContract::new(accounts(0));
+
+        env::state_write(&MetaDaoContractV0 {
+            admin: accounts(0),
+            epoch,
+            creator_funding: v0_creator_funding,
+            user_funds: contract.user_funds,
+            creators_per_epoch_set: contract.creators_per_epoch_set,
+            creators_metadata: v0_creators_metadata,
+            is_epoch_on: false,
+            in_registration: false,
+            in_funding: false,
+            in_minting: false,
+            protocol_allowed_tokens_fees,
+            tokens: contract.tokens,
+            metadata: contract.metadata,
+            nft_id: 1,
+        });
+    }
+
+    #[test]
+    fn test_migrate_from_v0_state() {
+        testing_env!(get_context("conliq.testnet".parse().unwrap(), 0));
+
+        write_v0_state();
+
+        let contract = MetaDaoContract::migrate();
+
+        assert_eq!(contract.admin, accounts(0));
+        assert_eq!(contract.epoch, Epoch(1));
+        assert_eq!(contract.nft_id, 1);
+        assert_eq!(
+            contract.get_epoch_phase(Epoch(1)),
+            Some(EpochPhase::Settled)
+        );
+        assert!(!contract.is_epoch_on());
+        assert_eq!(
+            contract
+                .protocol_allowed_tokens_fees
+                .get(&"wrap.near".parse().unwrap()),
+            Some(500)
+        );
+        assert!(contract.account_storage_usage > 0);
+
+        // legacy creators are only readable once migrated
+        assert!(contract
+            .creators_metadata
+            .get(&Epoch(1))
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            contract
+                .legacy_creators_metadata
+                .get(&Epoch(1))
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_migrate_legacy_creators() {
+        testing_env!(get_context("conliq.testnet".parse().unwrap(), 0));
+
+        write_v0_state();
+
+        let mut contract = MetaDaoContract::migrate();
+
+        assert!(matches!(
+            contract.migrate_legacy_creators(Epoch(1), None),
+            Err(MetaDaoError::InvalidAdminCall)
+        ));
+
+        testing_env!(get_context(accounts(0), 0));
+
+        assert_eq!(
+            contract.migrate_legacy_creators(Epoch(1), Some(1)).unwrap(),
+            1
+        );
+        assert_eq!(contract.migrate_legacy_creators(Epoch(1), None).unwrap(), 2);
+        assert!(matches!(
+            contract.migrate_legacy_creators(Epoch(1), None),
+            Err(MetaDaoError::NoLegacyCreatorsToMigrate)
+        ));
+
+        let metadata = CreatorMetadata::try_from(get_legacy_registry_metadata()).unwrap();
+        let creators_metadata = contract.creators_metadata.get(&Epoch(1)).unwrap();
+        assert_eq!(creators_metadata.get(&accounts(1)).unwrap(), metadata);
+        assert_eq!(creators_metadata.get(&accounts(2)).unwrap(), metadata);
+
+        let creator_funding = contract
+            .creator_funding
+            .get(&Epoch(1))
+            .unwrap()
+            .get(&accounts(1))
+            .unwrap();
+        assert_eq!(
+            creator_funding
+                .iter()
+                .map(|ot| (ot.edition, ot.minted, ot.payout_status))
+                .collect::<Vec<_>>(),
+            vec![
+                (1, true, PayoutStatus::Credited),
+                (1, false, PayoutStatus::Pending),
+                (2, false, PayoutStatus::Pending),
+            ]
+        );
+        assert!(contract.legacy_creators_metadata.get(&Epoch(1)).is_none());
+        assert!(contract.legacy_creator_funding.get(&Epoch(1)).is_none());
+
+        // the backers of creators without metadata are refunded instead
+        assert!(!contract.is_refundable(&Epoch(1), &accounts(1)));
+        assert!(contract.is_refundable(&Epoch(1), &accounts(3)));
+    }
+
+    #[test]
+    fn test_migrated_epoch_can_be_settled_and_archived() {
+        testing_env!(get_context("conliq.testnet".parse().unwrap(), 0));
+
+        write_v0_state();
+
+        let mut contract = MetaDaoContract::migrate();
+
+        testing_env!(get_context(accounts(0), parse_near!("0.1 N")));
+
+        assert!(matches!(
+            contract.archive_epoch(Epoch(1)),
+            Err(MetaDaoError::EpochCannotBeArchived)
+        ));
+
+        contract.migrate_legacy_creators(Epoch(1), None).unwrap();
+
+        // the entries which were not funded before the upgrade are still pending
+        assert!(matches!(
+            contract.archive_epoch(Epoch(1)),
+            Err(MetaDaoError::EpochCannotBeArchived)
+        ));
+
+        assert_eq!(
+            contract
+                .settle_creator(Epoch(1), accounts(1), None, None)
+                .unwrap(),
+            None
+        );
+        assert!(contract.is_creator_settled(Epoch(1), accounts(1)));
+        assert_eq!(contract.nft_id, 3);
+        assert_eq!(
+            contract.creator_balances.get(&accounts(1)).unwrap()[&"wrap.near".parse().unwrap()]
+                .claimable(),
+            190
+        );
+
+        contract
+            .refund_backers(Epoch(1), accounts(3), None, None)
+            .unwrap();
+
+        testing_env!(
+            get_context(accounts(0), 0),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_external_refund_ft_tokens_callback(Epoch(1), accounts(3), vec![0]);
+
+        contract.archive_epoch(Epoch(1)).unwrap();
+        assert!(contract.creator_funding.get(&Epoch(1)).is_none());
+    }
+}
@@ -7,173 +7,32 @@ use near_sdk::{
     serde::{Deserialize, Serialize},
     AccountId, Promise, PromiseOrValue,
 };
-use std::collections::HashMap;
 
-use crate::{error::MetaDaoError, FTAccountId, *};
-
-#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Debug, Clone, PartialEq)]
+use crate::{error::MetaDaoError, *};
+
+#[derive(
+    BorshDeserialize,
+    BorshSerialize,
+    Deserialize,
+    Serialize,
+    Debug,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+)]
 #[serde(crate = "near_sdk::serde")]
 pub enum UserNFTRank {
+    #[serde(alias = "common")]
     Common,
+    #[serde(alias = "uncommon")]
     Uncommon,
+    #[serde(alias = "rare")]
     Rare,
 }
 
-#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Debug, Clone, PartialEq)]
-#[serde(crate = "near_sdk::serde")]
-pub enum CreatorNFTRanking {
-    Common(HashMap<FTAccountId, u128>),
-    Uncommon(HashMap<FTAccountId, u128>),
-    Rare(HashMap<FTAccountId, u128>),
-}
-
-impl CreatorNFTRanking {
-    pub fn get_amount_from_nft_rank(
-        &self,
-        ft_token_id: &FTAccountId,
-    ) -> Result<u128, MetaDaoError> {
-        let inner = match self {
-            Self::Common(i) => i,
-            Self::Uncommon(i) => i,
-            Self::Rare(i) => i,
-        };
-
-        let amount = *inner
-            .get(ft_token_id)
-            .ok_or(MetaDaoError::InvalidFTTokenId)?;
-        Ok(amount)
-    }
-}
-
-#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Debug, Clone, PartialEq)]
-#[serde(crate = "near_sdk::serde")]
-pub enum CreatorNFTTitle {
-    Common(String),
-    Uncommon(String),
-    Rare(String),
-}
-
-impl CreatorNFTTitle {
-    pub fn get_title(&self) -> &str {
-        match self {
-            Self::Common(t) | Self::Uncommon(t) | Self::Rare(t) => t,
-        }
-    }
-}
-
-#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Debug, Clone, PartialEq)]
-#[serde(crate = "near_sdk::serde")]
-pub enum CreatorNFTDescription {
-    Common(String),
-    Uncommon(String),
-    Rare(String),
-}
-
-impl CreatorNFTDescription {
-    pub fn get_description(&self) -> &str {
-        match self {
-            Self::Common(t) | Self::Uncommon(t) | Self::Rare(t) => t,
-        }
-    }
-}
-
-#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Debug, Clone, PartialEq)]
-#[serde(crate = "near_sdk::serde")]
-pub enum CreatorNFTMedia {
-    Common(String),
-    Uncommon(String),
-    Rare(String),
-}
-
-impl CreatorNFTMedia {
-    pub fn get_media(&self) -> &str {
-        match self {
-            Self::Common(t) | Self::Uncommon(t) | Self::Rare(t) => t,
-        }
-    }
-}
-
-#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Debug, Clone, PartialEq)]
-#[serde(crate = "near_sdk::serde")]
-pub enum CreatorNFTCopies {
-    Common(u64),
-    Uncommon(u64),
-    Rare(u64),
-}
-
-impl CreatorNFTCopies {
-    pub fn get_copies(&self) -> u64 {
-        match self {
-            Self::Common(u) | Self::Uncommon(u) | Self::Rare(u) => *u,
-        }
-    }
-}
-
-#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Debug, Clone, PartialEq)]
-#[serde(crate = "near_sdk::serde")]
-pub enum CreatorNFTExtra {
-    Common(String),
-    Uncommon(String),
-    Rare(String),
-}
-
-impl CreatorNFTExtra {
-    pub fn get_extra(&self) -> &str {
-        match self {
-            Self::Common(t) | Self::Uncommon(t) | Self::Rare(t) => t,
-        }
-    }
-}
-
-#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Debug, Clone, PartialEq)]
-#[serde(crate = "near_sdk::serde")]
-pub enum CreatorNFTReference {
-    Common(Option<String>),
-    Uncommon(Option<String>),
-    Rare(Option<String>),
-}
-
-impl CreatorNFTReference {
-    pub fn get_reference(&self) -> Option<String> {
-        match self {
-            Self::Common(t) | Self::Uncommon(t) | Self::Rare(t) => t.clone(),
-        }
-    }
-}
-
-#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Debug, Clone, PartialEq)]
-#[serde(crate = "near_sdk::serde")]
-pub struct CreatorNFTRankings {
-    rankings: Vec<CreatorNFTRanking>,
-}
-
-impl CreatorNFTRankings {
-    pub fn get_ranking(&self, user_nft_rank: UserNFTRank) -> CreatorNFTRanking {
-        match user_nft_rank {
-            UserNFTRank::Common => self.rankings[0].clone(),
-            UserNFTRank::Uncommon => self.rankings[1].clone(),
-            UserNFTRank::Rare => self.rankings[2].clone(),
-        }
-    }
-}
-
-pub fn get_metadata(copies: u64, description: String, title: String) -> TokenMetadata {
-    TokenMetadata {
-        title: Some(title),
-        description: Some(description),
-        media: None,
-        media_hash: None,
-        copies: Some(copies),
-        issued_at: None,
-        expires_at: None,
-        starts_at: None,
-        updated_at: None,
-        extra: None,
-        reference: None,
-        reference_hash: None,
-    }
-}
-
 #[near_bindgen]
 impl MetaDaoContract {
     #[payable]
@@ -191,85 +50,50 @@ impl MetaDaoContract {
 
 #[near_bindgen]
 impl MetaDaoContract {
+    /// Returns the id of the NFT of `edition`, within `nft_rank`, minted for `user_id`
     pub fn get_token_id(
         &self,
         user_id: &UserAccountId,
         nft_rank: &UserNFTRank,
+        edition: u64,
         creator_metadata: &CreatorMetadata,
     ) -> TokenId {
         let nft_id = self.nft_id;
-        let titles = creator_metadata.get_titles();
-        let title = match nft_rank {
-            UserNFTRank::Common => titles[0].get_title(),
-            UserNFTRank::Uncommon => titles[1].get_title(),
-            UserNFTRank::Rare => titles[2].get_title(),
-        };
+        let title = creator_metadata
+            .tier(nft_rank)
+            .map(|tier| tier.title.as_str())
+            .unwrap_or_default();
         let nft_rank = match nft_rank {
             UserNFTRank::Common => "common",
             UserNFTRank::Uncommon => "uncommon",
             UserNFTRank::Rare => "rare",
         };
 
-        format!("MetaDao|{}|{}|{}|{}|", nft_id, title, nft_rank, user_id)
+        format!(
+            "MetaDao|{}|{}|{}|{}|{}|",
+            nft_id, title, nft_rank, edition, user_id
+        )
     }
 
+    /// Mints the NFTs and credits `creator_account_id`, for all its funding entries in `epoch`. Only
+    /// the creator, the admin or the settlement operator can call it, once `epoch` is in the minting
+    /// phase, for creators not settled yet. See [`MetaDaoContract::settle_creator`] to settle creators with many backers
     #[payable]
     #[handle_result]
     pub fn mint_nfts_for_users(
         &mut self,
+        epoch: Epoch,
         creator_account_id: CreatorAccountId,
     ) -> Result<(), MetaDaoError> {
-        let creators_metadata = self
-            .creators_metadata
-            .get(&self.epoch)
-            .ok_or(MetaDaoError::InvalidCurrentEpoch)?;
-
-        let creator_metadata = creators_metadata
-            .get(&creator_account_id)
-            .ok_or(MetaDaoError::CreatorIsNotRegistered)?;
+        self.assert_can_settle_creator(&creator_account_id)?;
+        self.assert_in_settlement_phase(&epoch)?;
 
-        let creators_obtained_funds = self
-            .creator_funding
-            .get(&self.epoch)
-            .ok_or(MetaDaoError::InvalidCurrentEpoch)?;
-
-        let creator_obtained_funds = creators_obtained_funds
-            .get(&creator_account_id)
-            .ok_or(MetaDaoError::CreatorIsNotRegistered)?;
-
-        for ObtainedTokenAmounts {
-            user_id,
-            ft_token_id,
-            amount,
-            nft_rank,
-            ..
-        } in creator_obtained_funds
-        {
-            let token_id = self.get_token_id(&user_id, &nft_rank, &creator_metadata);
-            let token_metadata = creator_metadata.get_token_metadata(nft_rank)?;
-
-            // minf nft for current user
-            self.nft_mint(token_id, user_id.clone(), token_metadata);
-
-            // get protocol fee
-            let protocol_fee = self
-                .protocol_allowed_tokens_fees
-                .get(&ft_token_id)
-                .ok_or(MetaDaoError::InvalidFTTokenId)?;
-
-            let creator_amount_to_receive = (amount as f64) * (1.0 - protocol_fee);
-
-            self.external_send_ft_tokens(
-                creator_account_id.clone(),
-                &user_id,
-                ft_token_id,
-                creator_amount_to_receive as u128,
-            );
-
-            // update nft id
-            self.nft_id += 1;
+        if self.is_creator_settled(epoch, creator_account_id.clone()) {
+            return Err(MetaDaoError::CreatorAlreadySettled);
         }
 
+        self.settle_creator(epoch, creator_account_id, None, None)?;
+
         Ok(())
     }
 }
@@ -288,7 +112,66 @@ near_contract_standards::impl_non_fungible_token_enumeration!(MetaDaoContract, t
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::tests::{fund_creator, get_context, get_contract_in_funding};
+    use near_contract_standards::non_fungible_token::enumeration::NonFungibleTokenEnumeration;
+    use near_sdk::json_types::U128;
+    use near_sdk::{test_utils::accounts, testing_env};
+
+    fn get_funded_contract() -> MetaDaoContract {
+        let mut contract = get_contract_in_funding(accounts(0));
+
+        fund_creator(
+            &mut contract,
+            &[("user.near", UserNFTRank::Common, "wrap.near", 100)],
+        );
+
+        contract
+    }
+
+    #[test]
+    fn test_mint_nfts_for_users_works() {
+        let mut contract = get_funded_contract();
+
+        assert!(matches!(
+            contract.mint_nfts_for_users(Epoch(1), accounts(1)),
+            Err(MetaDaoError::NotInMintingPeriod)
+        ));
+
+        contract.set_minting().unwrap();
+
+        testing_env!(get_context(accounts(1), parse_near!("0.1 N")));
+        contract.mint_nfts_for_users(Epoch(1), accounts(1)).unwrap();
+
+        assert_eq!(contract.nft_total_supply(), U128(1));
+        assert!(contract.is_creator_settled(Epoch(1), accounts(1)));
+
+        assert!(matches!(
+            contract.mint_nfts_for_users(Epoch(1), accounts(1)),
+            Err(MetaDaoError::CreatorAlreadySettled)
+        ));
+    }
 
     #[test]
-    fn test_mint_nfts_for_users_works() {}
+    fn test_mint_nfts_for_users_is_restricted() {
+        let mut contract = get_funded_contract();
+        contract.set_minting().unwrap();
+
+        testing_env!(get_context(accounts(2), parse_near!("0.1 N")));
+        assert!(matches!(
+            contract.mint_nfts_for_users(Epoch(1), accounts(1)),
+            Err(MetaDaoError::InvalidSettlementCall)
+        ));
+        assert!(matches!(
+            contract.set_settlement_operator(Some(accounts(2))),
+            Err(MetaDaoError::InvalidAdminCall)
+        ));
+
+        testing_env!(get_context(accounts(0), parse_near!("0.1 N")));
+        contract.set_settlement_operator(Some(accounts(2))).unwrap();
+
+        testing_env!(get_context(accounts(2), parse_near!("0.1 N")));
+        contract.mint_nfts_for_users(Epoch(1), accounts(1)).unwrap();
+
+        assert_eq!(contract.nft_total_supply(), U128(1));
+    }
 }
@@ -0,0 +1,230 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen};
+
+use crate::{error::MetaDaoError, *};
+
+/// [`EpochPhase`] tracks the lifecycle of an epoch. An epoch starts `Idle`, then
+/// moves through `Registration`, `Funding` and `Minting` before being `Settled`.
+/// It can be `Cancelled` at any point before settlement
+#[derive(
+    BorshDeserialize, BorshSerialize, Deserialize, Serialize, Debug, Clone, Copy, Eq, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum EpochPhase {
+    Idle,
+    Registration,
+    Funding,
+    Minting,
+    Settled,
+    Cancelled,
+}
+
+impl EpochPhase {
+    /// Settled and cancelled epochs are off, and cannot change phase anymore
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, Self::Settled | Self::Cancelled)
+    }
+
+    /// Checks if the epoch can move from the current phase to `to`, returning
+    /// the error that describes the illegal transition otherwise
+    pub fn check_transition(&self, to: EpochPhase) -> Result<(), MetaDaoError> {
+        use EpochPhase::*;
+
+        match (self, to) {
+            (Idle, Registration)
+            | (Registration, Funding)
+            | (Funding, Minting)
+            | (Minting, Settled)
+            | (Idle | Registration | Funding | Minting, Cancelled) => Ok(()),
+            (Settled | Cancelled, _) => Err(MetaDaoError::EpochIsOff),
+            (_, Idle) => Err(MetaDaoError::InvalidPhaseTransition),
+            (Idle, Funding) => Err(MetaDaoError::NotInRegistrationPeriod),
+            (Idle | Registration, Minting) => Err(MetaDaoError::NotInFundingPeriod),
+            (Idle, Settled) => Err(MetaDaoError::NotInMintingPeriod),
+            (Registration, _) => Err(MetaDaoError::AlreadyInRegistration),
+            (Funding, _) => Err(MetaDaoError::AlreadyInFunding),
+            (Minting, _) => Err(MetaDaoError::AlreadyInMinting),
+        }
+    }
+}
+
+impl MetaDaoContract {
+    /// Checks if the current epoch is ongoing, i.e. neither settled nor cancelled
+    pub fn is_epoch_on(&self) -> bool {
+        self.get_epoch_phase(self.epoch)
+            .map(|phase| !phase.is_terminal())
+            .unwrap_or(false)
+    }
+
+    /// Moves the current epoch to phase `to`, if the transition is legal
+    pub fn transition_epoch_phase(&mut self, to: EpochPhase) -> Result<(), MetaDaoError> {
+        let from = self
+            .get_epoch_phase(self.epoch)
+            .ok_or(MetaDaoError::EpochIsOff)?;
+
+        from.check_transition(to)?;
+
+        self.epoch_phases.insert(&self.epoch, &to);
+
+        MetaStageEvent::epoch_phase_change(self.epoch.count(), to).emit();
+
+        Ok(())
+    }
+
+    /// Checks if the current epoch is in registration. For scheduled epochs,
+    /// the registration window must also be open
+    pub fn is_in_registration(&self) -> bool {
+        self.get_epoch_phase(self.epoch) == Some(EpochPhase::Registration)
+            && self
+                .current_schedule()
+                .map(|schedule| schedule.is_in_registration(env::block_timestamp()))
+                .unwrap_or(true)
+    }
+
+    /// Checks if the current epoch is in funding. For scheduled epochs,
+    /// the funding window must also be open
+    pub fn is_in_funding(&self) -> bool {
+        self.get_epoch_phase(self.epoch) == Some(EpochPhase::Funding)
+            && self
+                .current_schedule()
+                .map(|schedule| schedule.is_in_funding(env::block_timestamp()))
+                .unwrap_or(true)
+    }
+
+    /// Checks if the current epoch is in minting
+    pub fn is_in_minting(&self) -> bool {
+        self.get_epoch_phase(self.epoch) == Some(EpochPhase::Minting)
+    }
+
+    /// Checks if the funding period of `epoch` is over
+    pub fn is_funding_over(&self, epoch: &Epoch) -> bool {
+        match self.get_epoch_phase(*epoch) {
+            Some(EpochPhase::Minting | EpochPhase::Settled | EpochPhase::Cancelled) => true,
+            Some(EpochPhase::Funding) => self
+                .epoch_schedules
+                .get(epoch)
+                .map(|schedule| schedule.is_funding_over(env::block_timestamp()))
+                .unwrap_or(false),
+            _ => false,
+        }
+    }
+}
+
+#[near_bindgen]
+impl MetaDaoContract {
+    /// Returns the phase of `epoch`. Scheduled epochs which were neither settled nor
+    /// cancelled are in the latest phase whose window has started
+    pub fn get_epoch_phase(&self, epoch: Epoch) -> Option<EpochPhase> {
+        let phase = self.epoch_phases.get(&epoch)?;
+
+        if phase.is_terminal() {
+            return Some(phase);
+        }
+
+        match self.epoch_schedules.get(&epoch) {
+            Some(schedule) => Some(schedule.phase_at(env::block_timestamp())),
+            None => Some(phase),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::get_context;
+    use near_sdk::test_utils::accounts;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn test_check_transition_works() {
+        use EpochPhase::*;
+
+        assert!(Idle.check_transition(Registration).is_ok());
+        assert!(Registration.check_transition(Funding).is_ok());
+        assert!(Funding.check_transition(Minting).is_ok());
+        assert!(Minting.check_transition(Settled).is_ok());
+
+        for phase in [Idle, Registration, Funding, Minting] {
+            assert!(phase.check_transition(Cancelled).is_ok());
+        }
+
+        assert!(matches!(
+            Idle.check_transition(Funding),
+            Err(MetaDaoError::NotInRegistrationPeriod)
+        ));
+        assert!(matches!(
+            Registration.check_transition(Minting),
+            Err(MetaDaoError::NotInFundingPeriod)
+        ));
+        assert!(matches!(
+            Idle.check_transition(Settled),
+            Err(MetaDaoError::NotInMintingPeriod)
+        ));
+        assert!(matches!(
+            Funding.check_transition(Registration),
+            Err(MetaDaoError::AlreadyInFunding)
+        ));
+        assert!(matches!(
+            Minting.check_transition(Minting),
+            Err(MetaDaoError::AlreadyInMinting)
+        ));
+        assert!(matches!(
+            Minting.check_transition(Idle),
+            Err(MetaDaoError::InvalidPhaseTransition)
+        ));
+        assert!(matches!(
+            Settled.check_transition(Cancelled),
+            Err(MetaDaoError::EpochIsOff)
+        ));
+        assert!(matches!(
+            Cancelled.check_transition(Registration),
+            Err(MetaDaoError::EpochIsOff)
+        ));
+    }
+
+    #[test]
+    fn test_epoch_lifecycle_works() {
+        testing_env!(get_context(accounts(1), 0));
+
+        let mut contract = MetaDaoContract::new(accounts(1));
+
+        assert_eq!(contract.get_epoch_phase(Epoch(1)), None);
+        assert!(!contract.is_epoch_on());
+
+        contract.create_new_epoch(None, None).unwrap();
+        assert_eq!(contract.get_epoch_phase(Epoch(1)), Some(EpochPhase::Idle));
+        assert!(contract.is_epoch_on());
+
+        contract.set_registration().unwrap();
+        assert_eq!(
+            contract.get_epoch_phase(Epoch(1)),
+            Some(EpochPhase::Registration)
+        );
+
+        contract.set_funding().unwrap();
+        assert!(contract.is_in_funding());
+
+        contract.set_minting().unwrap();
+        assert!(contract.is_in_minting());
+        assert!(contract.is_funding_over(&Epoch(1)));
+
+        contract.end_epoch().unwrap();
+        assert_eq!(
+            contract.get_epoch_phase(Epoch(1)),
+            Some(EpochPhase::Settled)
+        );
+        assert!(!contract.is_epoch_on());
+
+        contract.create_new_epoch(None, None).unwrap();
+        contract.cancel_epoch().unwrap();
+        assert_eq!(
+            contract.get_epoch_phase(Epoch(2)),
+            Some(EpochPhase::Cancelled)
+        );
+        assert_eq!(
+            contract.get_epoch_phase(Epoch(1)),
+            Some(EpochPhase::Settled)
+        );
+    }
+}
@@ -0,0 +1,172 @@
+use near_sdk::serde::Deserialize;
+use near_sdk::serde_json;
+
+use crate::{
+    consts::{MAX_PLEDGE_QUANTITY, PLEDGE_MESSAGE_VERSION},
+    error::MetaDaoError,
+    nft::UserNFTRank,
+    CreatorAccountId, Epoch,
+};
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(rename_all = "snake_case")]
+pub enum PledgeAction {
+    Pledge,
+}
+
+/// [`PledgeMessage`] is the payload of the `msg` of an `ft_transfer_call` to the contract.
+/// It is either a JSON object, e.g.
+/// `{"version":1,"action":"pledge","creator":"my_band.near","rank":"rare","epoch":3,"quantity":2}`,
+/// where `version`, `epoch` and `quantity` are optional, or the legacy `<creator>_<rank>` format
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct PledgeMessage {
+    #[serde(default = "default_pledge_message_version")]
+    pub version: u8,
+    pub action: PledgeAction,
+    pub creator: CreatorAccountId,
+    pub rank: UserNFTRank,
+    #[serde(default)]
+    pub epoch: Option<Epoch>,
+    #[serde(default = "default_pledge_quantity")]
+    pub quantity: u32,
+}
+
+fn default_pledge_message_version() -> u8 {
+    PLEDGE_MESSAGE_VERSION
+}
+
+fn default_pledge_quantity() -> u32 {
+    1
+}
+
+impl PledgeMessage {
+    /// A pledge for a single NFT of tier `rank` of `creator`, in the current epoch
+    pub fn new(creator: CreatorAccountId, rank: UserNFTRank) -> Self {
+        Self {
+            version: PLEDGE_MESSAGE_VERSION,
+            action: PledgeAction::Pledge,
+            creator,
+            rank,
+            epoch: None,
+            quantity: 1,
+        }
+    }
+
+    pub fn parse(msg: &str) -> Result<Self, MetaDaoError> {
+        let message = if msg.trim_start().starts_with('{') {
+            serde_json::from_str::<Self>(msg).map_err(|_| MetaDaoError::InvalidPledgeMessage)?
+        } else {
+            Self::parse_legacy(msg)?
+        };
+
+        message.validate()?;
+
+        Ok(message)
+    }
+
+    /// Parses the legacy `<creator>_<rank>` format. The message is split on its last `_`,
+    /// so that creator account ids containing underscores are supported
+    fn parse_legacy(msg: &str) -> Result<Self, MetaDaoError> {
+        let (creator, rank) = msg
+            .rsplit_once('_')
+            .ok_or(MetaDaoError::InvalidPledgeMessage)?;
+
+        let creator = CreatorAccountId::try_from(creator.to_string())
+            .map_err(|_| MetaDaoError::InvalidPledgeMessage)?;
+
+        let rank = match rank {
+            "common" => UserNFTRank::Common,
+            "uncommon" => UserNFTRank::Uncommon,
+            "rare" => UserNFTRank::Rare,
+            _ => return Err(MetaDaoError::InvalidPledgeMessage),
+        };
+
+        Ok(Self::new(creator, rank))
+    }
+
+    fn validate(&self) -> Result<(), MetaDaoError> {
+        if self.version != PLEDGE_MESSAGE_VERSION {
+            return Err(MetaDaoError::UnsupportedPledgeMessageVersion);
+        }
+
+        if self.quantity == 0 || self.quantity > MAX_PLEDGE_QUANTITY {
+            return Err(MetaDaoError::InvalidPledgeMessage);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json_pledge_message_works() {
+        let message = PledgeMessage::parse(
+            r#"{"action":"pledge","creator":"my_band.near","rank":"rare","epoch":3,"quantity":2}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            message,
+            PledgeMessage {
+                version: PLEDGE_MESSAGE_VERSION,
+                action: PledgeAction::Pledge,
+                creator: "my_band.near".to_string().try_into().unwrap(),
+                rank: UserNFTRank::Rare,
+                epoch: Some(Epoch(3)),
+                quantity: 2,
+            }
+        );
+
+        let message = PledgeMessage::parse(
+            r#"{"version":1,"action":"pledge","creator":"a.near","rank":"Common"}"#,
+        )
+        .unwrap();
+
+        assert_eq!(message.rank, UserNFTRank::Common);
+        assert_eq!(message.epoch, None);
+        assert_eq!(message.quantity, 1);
+    }
+
+    #[test]
+    fn test_parse_legacy_pledge_message_works() {
+        let message = PledgeMessage::parse("my_band.near_uncommon").unwrap();
+
+        assert_eq!(
+            message.creator,
+            "my_band.near".to_string().try_into().unwrap()
+        );
+        assert_eq!(message.rank, UserNFTRank::Uncommon);
+        assert_eq!(message.quantity, 1);
+    }
+
+    #[test]
+    fn test_parse_pledge_message_fails_if_malformed() {
+        for msg in [
+            "",
+            "creator.near",
+            "-/lj_common",
+            "creator.near_legendary",
+            r#"{"action":"pledge","creator":"creator.near"}"#,
+            r#"{"action":"withdraw","creator":"creator.near","rank":"rare"}"#,
+            r#"{"action":"pledge","creator":"creator.near","rank":"rare","quantity":0}"#,
+            r#"{"action":"pledge","#,
+        ] {
+            assert!(matches!(
+                PledgeMessage::parse(msg),
+                Err(MetaDaoError::InvalidPledgeMessage)
+            ));
+        }
+
+        assert!(matches!(
+            PledgeMessage::parse(
+                r#"{"version":2,"action":"pledge","creator":"a.near","rank":"rare"}"#
+            ),
+            Err(MetaDaoError::UnsupportedPledgeMessageVersion)
+        ));
+    }
+}
@@ -0,0 +1,200 @@
+use near_contract_standards::non_fungible_token::core::NonFungibleTokenCore;
+use near_contract_standards::non_fungible_token::{Token, TokenId};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::collections::Vector;
+use near_sdk::json_types::U128;
+use near_sdk::near_bindgen;
+use near_sdk::serde::{Deserialize, Serialize};
+
+use crate::{error::MetaDaoError, *};
+
+/// [`NFTProvenance`] records where a minted NFT comes from: the epoch, creator and tier,
+/// and the pledge which funded it
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct NFTProvenance {
+    pub epoch: Epoch,
+    pub creator_id: CreatorAccountId,
+    pub nft_rank: UserNFTRank,
+    pub edition: u64,
+    /// The backer whose pledge funded the NFT
+    pub user_id: UserAccountId,
+    pub ft_token_id: FTAccountId,
+    pub amount: U128,
+    /// The index of the pledge among the funding entries of the creator, in the epoch
+    pub funding_index: u64,
+}
+
+impl MetaDaoContract {
+    /// Records the provenance of the NFT `token_id`, and lists it among the creator NFTs of the epoch
+    pub fn record_nft_provenance(
+        &mut self,
+        token_id: &TokenId,
+        provenance: NFTProvenance,
+    ) -> Result<(), MetaDaoError> {
+        let mut creator_tokens = self
+            .creator_tokens
+            .get(&provenance.epoch)
+            .ok_or(MetaDaoError::InvalidCurrentEpoch)?;
+
+        // each creator lists its NFTs in a collection of its own, so that minting
+        // does not rewrite the whole list
+        let mut token_ids = creator_tokens
+            .get(&provenance.creator_id)
+            .unwrap_or_else(|| {
+                Vector::new(
+                    format!(
+                        "creator token ids for epoch: {} creator: {}",
+                        provenance.epoch.count(),
+                        provenance.creator_id
+                    )
+                    .as_bytes()
+                    .to_vec(),
+                )
+            });
+        token_ids.push(token_id);
+
+        creator_tokens.insert(&provenance.creator_id, &token_ids);
+        self.creator_tokens
+            .insert(&provenance.epoch, &creator_tokens);
+
+        self.nft_provenance.insert(token_id, &provenance);
+
+        Ok(())
+    }
+}
+
+#[near_bindgen]
+impl MetaDaoContract {
+    /// Returns the epoch, creator, tier and pledge the NFT `token_id` was minted for, if any
+    pub fn nft_provenance(&self, token_id: TokenId) -> Option<NFTProvenance> {
+        self.nft_provenance.get(&token_id)
+    }
+
+    /// Returns the NFTs minted for the backers of `creator_account_id` in `epoch`, in mint order,
+    /// paginated via `from_index` and `limit`
+    pub fn nft_tokens_for_creator(
+        &self,
+        creator_account_id: CreatorAccountId,
+        epoch: Epoch,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Vec<Token> {
+        let token_ids = match self
+            .creator_tokens
+            .get(&epoch)
+            .and_then(|creator_tokens| creator_tokens.get(&creator_account_id))
+        {
+            Some(token_ids) => token_ids,
+            None => return vec![],
+        };
+
+        let from_index = from_index.unwrap_or(0).min(token_ids.len());
+        let to_index = from_index
+            .saturating_add(limit.unwrap_or(u64::MAX))
+            .min(token_ids.len());
+
+        (from_index..to_index)
+            .filter_map(|index| token_ids.get(index))
+            .filter_map(|token_id| self.nft_token(token_id))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::registry::CreatorMetadata;
+    use crate::tests::{get_allowed_registry_metadata, get_context};
+    use near_sdk::{test_utils::accounts, testing_env};
+
+    #[test]
+    fn test_nft_provenance_and_tokens_for_creator() {
+        let admin: AccountId = accounts(1);
+        testing_env!(get_context(admin.clone(), parse_near!("0.1 N")));
+
+        let mut contract = MetaDaoContract::new(admin);
+
+        let mut protocol_accounts_fees = HashMap::<FTAccountId, u16>::new();
+
+        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 500);
+        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 300);
+
+        contract
+            .create_new_epoch(Some(protocol_accounts_fees), None)
+            .unwrap();
+
+        contract.set_registration().unwrap();
+
+        // titles may contain the separator of token ids
+        let mut tiers = get_allowed_registry_metadata(&contract)
+            .get_tiers()
+            .to_vec();
+        tiers[1].title = "un|common".to_string();
+
+        contract
+            .creator_registration(CreatorMetadata::new(tiers), None)
+            .unwrap();
+        contract.set_funding().unwrap();
+
+        for (user, nft_rank, ft_token_id, amount) in [
+            ("user.near", UserNFTRank::Common, "wrap.near", 100_u128),
+            ("user.near", UserNFTRank::Uncommon, "usn", 2_500_u128),
+            (
+                "other_user.near",
+                UserNFTRank::Uncommon,
+                "wrap.near",
+                250_u128,
+            ),
+        ] {
+            contract
+                .user_funding_creator(
+                    user.to_string().try_into().unwrap(),
+                    accounts(1),
+                    nft_rank,
+                    amount,
+                    ft_token_id.to_string().try_into().unwrap(),
+                )
+                .unwrap();
+        }
+
+        contract.set_minting().unwrap();
+        contract
+            .settle_creator(Epoch(1), accounts(1), None, None)
+            .unwrap();
+
+        let tokens = contract.nft_tokens_for_creator(accounts(1), Epoch(1), None, None);
+        assert_eq!(tokens.len(), 3);
+
+        let provenance = contract.nft_provenance(tokens[2].token_id.clone()).unwrap();
+        assert_eq!(
+            provenance,
+            NFTProvenance {
+                epoch: Epoch(1),
+                creator_id: accounts(1),
+                nft_rank: UserNFTRank::Uncommon,
+                edition: 2,
+                user_id: "other_user.near".parse().unwrap(),
+                ft_token_id: "wrap.near".parse().unwrap(),
+                amount: U128(250),
+                funding_index: 2,
+            }
+        );
+        assert_eq!(tokens[2].owner_id, provenance.user_id);
+
+        let page = contract.nft_tokens_for_creator(accounts(1), Epoch(1), Some(1), Some(1));
+        assert_eq!(page.len(), 1);
+        assert_eq!(page[0].token_id, tokens[1].token_id);
+        assert!(contract
+            .nft_tokens_for_creator(accounts(1), Epoch(1), Some(3), None)
+            .is_empty());
+
+        assert!(contract
+            .nft_tokens_for_creator(accounts(2), Epoch(1), None, None)
+            .is_empty());
+        assert!(contract
+            .nft_tokens_for_creator(accounts(1), Epoch(2), None, None)
+            .is_empty());
+        assert_eq!(contract.nft_provenance("unknown".to_string()), None);
+    }
+}
@@ -0,0 +1,772 @@
+use crate::events::{CreatorDeregistrationLog, TransferLog};
+use crate::{consts::GAS_FOR_FT_TRANSFER, *};
+use near_sdk::json_types::U128;
+use near_sdk::{env, Promise, PromiseResult};
+
+/// A user's refund of a single fungible token, aggregated over
+/// the [`ObtainedTokenAmounts`] entries at `entry_indices`
+struct UserRefund {
+    user_id: UserAccountId,
+    ft_token_id: FTAccountId,
+    amount: u128,
+    entry_indices: Vec<u64>,
+}
+
+impl MetaDaoContract {
+    /// Checks if the backers of `creator_account_id` are allowed to claim
+    /// refunds for `epoch`, either because the epoch was cancelled, because
+    /// the creator was deregistered or because it missed its funding goal
+    pub fn is_refundable(&self, epoch: &Epoch, creator_account_id: &CreatorAccountId) -> bool {
+        self.get_epoch_phase(*epoch) == Some(EpochPhase::Cancelled)
+            || self
+                .refundable_creators
+                .get(epoch)
+                .map(|creators| creators.contains(creator_account_id))
+                .unwrap_or(false)
+            || (self.is_funding_over(epoch) && !self.is_funding_goal_met(epoch, creator_account_id))
+    }
+
+    /// Checks that no funding entry of `epoch`, or only of `creator_account_id` if set, had its NFT
+    /// minted or was credited to its creator, so that its backers can still be refunded
+    fn assert_settlement_not_started(
+        &self,
+        epoch: &Epoch,
+        creator_account_id: Option<&CreatorAccountId>,
+    ) -> Result<(), MetaDaoError> {
+        let creator_fundings = self
+            .creator_funding
+            .get(epoch)
+            .ok_or(MetaDaoError::InvalidCurrentEpoch)?;
+
+        let is_settled = |ot: &ObtainedTokenAmounts| {
+            !ot.already_refunded && (ot.minted || ot.payout_status != PayoutStatus::Pending)
+        };
+
+        let settlement_started = match creator_account_id {
+            Some(creator_account_id) => creator_fundings
+                .get(creator_account_id)
+                .map(|creator_funding| creator_funding.iter().any(is_settled))
+                .unwrap_or(false),
+            None => creator_fundings
+                .values()
+                .any(|creator_funding| creator_funding.iter().any(is_settled)),
+        };
+
+        if settlement_started {
+            return Err(MetaDaoError::SettlementAlreadyStarted);
+        }
+
+        Ok(())
+    }
+
+    /// Marks every refundable entry of `creator_account_id` in range `[from_index, from_index + limit)`,
+    /// optionally restricted to `user_id`, as refunded and sends the tokens back to their backers
+    fn internal_refund(
+        &mut self,
+        epoch: Epoch,
+        creator_account_id: CreatorAccountId,
+        user_id: Option<&UserAccountId>,
+        from_index: u64,
+        limit: u64,
+    ) -> Result<Promise, MetaDaoError> {
+        if !self.is_refundable(&epoch, &creator_account_id) {
+            return Err(MetaDaoError::FundsNotRefundable);
+        }
+
+        let mut creator_fundings = self
+            .creator_funding
+            .get(&epoch)
+            .ok_or(MetaDaoError::InvalidCurrentEpoch)?;
+
+        let mut creator_funding = creator_fundings
+            .get(&creator_account_id)
+            .ok_or(MetaDaoError::CreatorIsNotRegistered)?;
+
+        let mut refunds: Vec<UserRefund> = vec![];
+
+        for (index, obtained_amount) in creator_funding
+            .iter_mut()
+            .enumerate()
+            .skip(from_index as usize)
+            .take(limit as usize)
+        {
+            // entries credited to the creator, or whose NFT was minted, were already settled
+            if obtained_amount.already_refunded
+                || obtained_amount.minted
+                || obtained_amount.payout_status != PayoutStatus::Pending
+            {
+                continue;
+            }
+
+            if matches!(user_id, Some(id) if *id != obtained_amount.user_id) {
+                continue;
+            }
+
+            obtained_amount.already_refunded = true;
+            self.release_refunded_pledge(epoch, &creator_account_id, obtained_amount);
+
+            match refunds.iter_mut().find(|r| {
+                r.user_id == obtained_amount.user_id && r.ft_token_id == obtained_amount.ft_token_id
+            }) {
+                Some(refund) => {
+                    refund.amount += obtained_amount.amount;
+                    refund.entry_indices.push(index as u64);
+                }
+                None => refunds.push(UserRefund {
+                    user_id: obtained_amount.user_id.clone(),
+                    ft_token_id: obtained_amount.ft_token_id.clone(),
+                    amount: obtained_amount.amount,
+                    entry_indices: vec![index as u64],
+                }),
+            }
+        }
+
+        if refunds.is_empty() {
+            return Err(MetaDaoError::NoFundsToRefund);
+        }
+
+        creator_fundings.insert(&creator_account_id, &creator_funding);
+        self.creator_funding.insert(&epoch, &creator_fundings);
+
+        let pending_refunds = self.pending_refunds.get(&epoch).unwrap_or(0);
+        self.pending_refunds
+            .insert(&epoch, &(pending_refunds + refunds.len() as u64));
+
+        MetaStageEvent::Refund(
+            refunds
+                .iter()
+                .map(|refund| TransferLog {
+                    epoch: epoch.count(),
+                    creator_id: creator_account_id.clone(),
+                    user_id: refund.user_id.clone(),
+                    ft_token_id: refund.ft_token_id.clone(),
+                    amount: U128(refund.amount),
+                })
+                .collect(),
+        )
+        .emit();
+
+        let promise = refunds
+            .into_iter()
+            .map(|refund| self.external_refund_ft_tokens(epoch, creator_account_id.clone(), refund))
+            .reduce(|a, b| a.and(b))
+            .expect("MetaDaoContract::internal_refund: refunds are non empty");
+
+        Ok(promise)
+    }
+
+    /// Removes the refunded pledge of `obtained_amount` from the funds of its backer, and gives
+    /// its copy back to the tier of `creator_account_id`, in `epoch`
+    fn release_refunded_pledge(
+        &mut self,
+        epoch: Epoch,
+        creator_account_id: &CreatorAccountId,
+        obtained_amount: &ObtainedTokenAmounts,
+    ) {
+        if let Some(mut user_funds) = self.user_funds.get(&epoch) {
+            if let Some(mut funds) = user_funds.get(&obtained_amount.user_id) {
+                if let Some(position) = funds.iter().position(|funded| {
+                    funded.creator_id == *creator_account_id
+                        && funded.ft_token_id == obtained_amount.ft_token_id
+                        && funded.amount == obtained_amount.amount
+                }) {
+                    funds.remove(position);
+                }
+
+                if funds.is_empty() {
+                    user_funds.remove(&obtained_amount.user_id);
+                } else {
+                    user_funds.insert(&obtained_amount.user_id, &funds);
+                }
+
+                self.user_funds.insert(&epoch, &user_funds);
+            }
+        }
+
+        if let Some(mut tier_sold_copies) = self.tier_sold_copies.get(&epoch) {
+            if let Some(mut sold_copies) = tier_sold_copies.get(creator_account_id) {
+                if let Some(sold) = sold_copies.get_mut(&obtained_amount.nft_rank) {
+                    *sold = sold.saturating_sub(1);
+                }
+
+                tier_sold_copies.insert(creator_account_id, &sold_copies);
+                self.tier_sold_copies.insert(&epoch, &tier_sold_copies);
+            }
+        }
+    }
+
+    /// Records back the pledge of `obtained_amount`, whose refund failed, in the funds of its
+    /// backer and the sold copies of its tier
+    fn restore_refunded_pledge(
+        &mut self,
+        epoch: Epoch,
+        creator_account_id: &CreatorAccountId,
+        obtained_amount: &ObtainedTokenAmounts,
+    ) {
+        if let Some(mut user_funds) = self.user_funds.get(&epoch) {
+            let mut funds = user_funds.get(&obtained_amount.user_id).unwrap_or_default();
+            funds.push(FundedTokenAmount {
+                creator_id: creator_account_id.clone(),
+                ft_token_id: obtained_amount.ft_token_id.clone(),
+                amount: obtained_amount.amount,
+            });
+
+            user_funds.insert(&obtained_amount.user_id, &funds);
+            self.user_funds.insert(&epoch, &user_funds);
+        }
+
+        if let Some(mut tier_sold_copies) = self.tier_sold_copies.get(&epoch) {
+            let mut sold_copies = tier_sold_copies.get(creator_account_id).unwrap_or_default();
+            *sold_copies
+                .entry(obtained_amount.nft_rank.clone())
+                .or_insert(0) += 1;
+
+            tier_sold_copies.insert(creator_account_id, &sold_copies);
+            self.tier_sold_copies.insert(&epoch, &tier_sold_copies);
+        }
+    }
+
+    fn external_refund_ft_tokens(
+        &self,
+        epoch: Epoch,
+        creator_account_id: CreatorAccountId,
+        refund: UserRefund,
+    ) -> Promise {
+        self.internal_transfer(refund.user_id, refund.ft_token_id, refund.amount)
+            .then(
+                Self::ext(env::current_account_id())
+                    .with_static_gas(GAS_FOR_FT_TRANSFER)
+                    .on_external_refund_ft_tokens_callback(
+                        epoch,
+                        creator_account_id,
+                        refund.entry_indices,
+                    ),
+            )
+    }
+}
+
+#[near_bindgen]
+impl MetaDaoContract {
+    /// Aborts the current epoch. All backers of the epoch are allowed to claim refunds, so it
+    /// is rejected once any NFT of the epoch was minted, or any funds credited to a creator
+    #[handle_result]
+    pub fn cancel_epoch(&mut self) -> Result<(), MetaDaoError> {
+        if env::predecessor_account_id() != self.admin {
+            return Err(MetaDaoError::InvalidAdminCall);
+        }
+
+        if !self.is_epoch_on() {
+            return Err(MetaDaoError::EpochIsOff);
+        }
+
+        self.assert_settlement_not_started(&self.epoch, None)?;
+
+        self.transition_epoch_phase(EpochPhase::Cancelled)
+    }
+
+    /// Removes `creator_account_id` from the current epoch. Its backers are allowed to claim refunds,
+    /// so it is rejected once any of its NFTs was minted, or any funds credited to it. Its fungible
+    /// token storage reserve is sent back, unless it has fungible tokens left to withdraw
+    #[handle_result]
+    pub fn deregister_creator(
+        &mut self,
+        creator_account_id: CreatorAccountId,
+    ) -> Result<(), MetaDaoError> {
+        if env::predecessor_account_id() != self.admin {
+            return Err(MetaDaoError::InvalidAdminCall);
+        }
+
+        if !self.is_epoch_on() {
+            return Err(MetaDaoError::EpochIsOff);
+        }
+
+        self.assert_settlement_not_started(&self.epoch, Some(&creator_account_id))?;
+
+        let mut creators_per_epoch = self
+            .creators_per_epoch_set
+            .get(&self.epoch)
+            .ok_or(MetaDaoError::InvalidCurrentEpoch)?;
+
+        if !creators_per_epoch.remove(&creator_account_id) {
+            return Err(MetaDaoError::CreatorIsNotRegistered);
+        }
+
+        let mut creators_metadata = self
+            .creators_metadata
+            .get(&self.epoch)
+            .ok_or(MetaDaoError::InvalidCurrentEpoch)?;
+
+        let mut refundable_creators = self
+            .refundable_creators
+            .get(&self.epoch)
+            .ok_or(MetaDaoError::InvalidCurrentEpoch)?;
+
+        creators_metadata.remove(&creator_account_id);
+        refundable_creators.insert(&creator_account_id);
+
+        self.creators_per_epoch_set
+            .insert(&self.epoch, &creators_per_epoch);
+        self.creators_metadata
+            .insert(&self.epoch, &creators_metadata);
+        self.refundable_creators
+            .insert(&self.epoch, &refundable_creators);
+
+        self.release_ft_storage_reserve(&creator_account_id);
+
+        MetaStageEvent::CreatorDeregistration(vec![CreatorDeregistrationLog {
+            epoch: self.epoch.count(),
+            creator_id: creator_account_id,
+        }])
+        .emit();
+
+        Ok(())
+    }
+
+    /// Sends back to the caller all the funds it provided to `creator_account_id` in `epoch`
+    #[handle_result]
+    pub fn claim_refund(
+        &mut self,
+        epoch: Epoch,
+        creator_account_id: CreatorAccountId,
+    ) -> Result<Promise, MetaDaoError> {
+        let user_id = env::predecessor_account_id();
+        self.internal_refund(epoch, creator_account_id, Some(&user_id), 0, u64::MAX)
+    }
+
+    /// Sends back the funds of every backer of `creator_account_id` in `epoch`, paginated
+    /// over the creator funding entries
+    #[handle_result]
+    pub fn refund_backers(
+        &mut self,
+        epoch: Epoch,
+        creator_account_id: CreatorAccountId,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Promise, MetaDaoError> {
+        if env::predecessor_account_id() != self.admin {
+            return Err(MetaDaoError::InvalidAdminCall);
+        }
+
+        self.internal_refund(
+            epoch,
+            creator_account_id,
+            None,
+            from_index.unwrap_or(0),
+            limit.unwrap_or(u64::MAX),
+        )
+    }
+
+    /// Restores the refunded entries, if the refund transfer failed, so they can be claimed again.
+    /// Until then, the epoch cannot be archived
+    #[private]
+    pub fn on_external_refund_ft_tokens_callback(
+        &mut self,
+        epoch: Epoch,
+        creator_account_id: CreatorAccountId,
+        entry_indices: Vec<u64>,
+    ) {
+        if env::promise_results_count() != 1 {
+            env::panic_str("MetaDaoContract::external_refund_ft_tokens::Invalid promise result count, one should only have one promise result");
+        }
+
+        match self.pending_refunds.get(&epoch).unwrap_or(0) {
+            0 | 1 => self.pending_refunds.remove(&epoch),
+            pending_refunds => self.pending_refunds.insert(&epoch, &(pending_refunds - 1)),
+        };
+
+        if matches!(env::promise_result(0), PromiseResult::Successful(_)) {
+            return;
+        }
+
+        let mut creator_fundings = self
+            .creator_funding
+            .get(&epoch)
+            .expect("MetaDaoContract::external_refund_ft_tokens::Invalid epoch id");
+
+        let mut creator_funding = creator_fundings
+            .get(&creator_account_id)
+            .expect("MetaDaoContract::external_refund_ft_tokens::Creator is not registered");
+
+        let mut failed_refunds = vec![];
+
+        for index in entry_indices {
+            if let Some(obtained_amount) = creator_funding.get_mut(index as usize) {
+                obtained_amount.already_refunded = false;
+                self.restore_refunded_pledge(epoch, &creator_account_id, obtained_amount);
+
+                failed_refunds.push(TransferLog {
+                    epoch: epoch.count(),
+                    creator_id: creator_account_id.clone(),
+                    user_id: obtained_amount.user_id.clone(),
+                    ft_token_id: obtained_amount.ft_token_id.clone(),
+                    amount: U128(obtained_amount.amount),
+                });
+            }
+        }
+
+        creator_fundings.insert(&creator_account_id, &creator_funding);
+        self.creator_funding.insert(&epoch, &creator_fundings);
+
+        MetaStageEvent::RefundFailed(failed_refunds).emit();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{
+        fund_creator, get_allowed_registry_metadata, get_context, get_contract_in_funding,
+    };
+    use near_sdk::{test_utils::accounts, testing_env, PromiseResult, RuntimeFeesConfig, VMConfig};
+
+    fn get_funded_contract() -> MetaDaoContract {
+        let mut contract = get_contract_in_funding(accounts(1));
+
+        fund_creator(
+            &mut contract,
+            &[
+                ("user.near", UserNFTRank::Common, "wrap.near", 100),
+                ("user.near", UserNFTRank::Common, "wrap.near", 250),
+                ("user.near", UserNFTRank::Common, "usn", 1_000),
+                ("other_user.near", UserNFTRank::Common, "wrap.near", 500),
+            ],
+        );
+
+        contract
+    }
+
+    fn refunded_entries(contract: &MetaDaoContract) -> Vec<bool> {
+        contract
+            .creator_funding
+            .get(&Epoch(1))
+            .unwrap()
+            .get(&accounts(1))
+            .unwrap()
+            .iter()
+            .map(|ot| ot.already_refunded)
+            .collect()
+    }
+
+    #[test]
+    fn test_cancel_epoch_works() {
+        let mut contract = get_funded_contract();
+
+        contract.cancel_epoch().unwrap();
+
+        assert!(!contract.is_epoch_on());
+        assert_eq!(
+            contract.get_epoch_phase(Epoch(1)),
+            Some(EpochPhase::Cancelled)
+        );
+        assert!(contract.is_refundable(&Epoch(1), &accounts(1)));
+    }
+
+    #[test]
+    fn test_deregister_creator_works() {
+        let mut contract = get_funded_contract();
+
+        contract.deregister_creator(accounts(1)).unwrap();
+
+        assert!(!contract
+            .creators_per_epoch_set
+            .get(&Epoch(1))
+            .unwrap()
+            .contains(&accounts(1)));
+        assert!(contract.is_refundable(&Epoch(1), &accounts(1)));
+        assert_eq!(
+            contract.get_epoch_phase(Epoch(1)),
+            Some(EpochPhase::Funding)
+        );
+    }
+
+    #[test]
+    fn test_cancel_epoch_fails_once_an_nft_is_claimed() {
+        let mut contract = get_funded_contract();
+        contract.set_minting().unwrap();
+
+        testing_env!(get_context(
+            "other_user.near".parse().unwrap(),
+            parse_near!("0.1 N")
+        ));
+        contract.claim_nft(Epoch(1), accounts(1)).unwrap();
+
+        testing_env!(get_context(accounts(1), 0));
+        assert!(matches!(
+            contract.cancel_epoch(),
+            Err(MetaDaoError::SettlementAlreadyStarted)
+        ));
+        assert_eq!(
+            contract.get_epoch_phase(Epoch(1)),
+            Some(EpochPhase::Minting)
+        );
+    }
+
+    #[test]
+    fn test_deregister_creator_fails_once_its_payout_is_released() {
+        let mut contract = get_funded_contract();
+        contract.set_minting().unwrap();
+
+        contract
+            .release_creator_payout(Epoch(1), accounts(1), None, None)
+            .unwrap();
+
+        assert!(matches!(
+            contract.deregister_creator(accounts(1)),
+            Err(MetaDaoError::SettlementAlreadyStarted)
+        ));
+        assert!(!contract.is_refundable(&Epoch(1), &accounts(1)));
+    }
+
+    #[test]
+    fn test_deregister_creator_fails_if_not_admin() {
+        let mut contract = get_funded_contract();
+
+        testing_env!(get_context(accounts(2), 0));
+
+        assert!(contract
+            .deregister_creator(accounts(1))
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid Admin call"));
+    }
+
+    #[test]
+    fn test_claim_refund_works() {
+        let mut contract = get_funded_contract();
+
+        contract.cancel_epoch().unwrap();
+
+        testing_env!(get_context("user.near".to_string().try_into().unwrap(), 0));
+
+        contract.claim_refund(Epoch(1), accounts(1)).unwrap();
+
+        assert_eq!(refunded_entries(&contract), vec![true, true, true, false]);
+
+        assert!(matches!(
+            contract.claim_refund(Epoch(1), accounts(1)),
+            Err(MetaDaoError::NoFundsToRefund)
+        ));
+    }
+
+    #[test]
+    fn test_claim_refund_releases_user_funds_and_tier_copies() {
+        let mut contract = get_funded_contract();
+        let user_id: AccountId = "user.near".to_string().try_into().unwrap();
+
+        contract.cancel_epoch().unwrap();
+
+        testing_env!(get_context(user_id.clone(), 0));
+
+        contract.claim_refund(Epoch(1), accounts(1)).unwrap();
+
+        let user_funds = contract.user_funds.get(&Epoch(1)).unwrap();
+        assert!(user_funds.get(&user_id).is_none());
+        assert_eq!(
+            user_funds
+                .get(&"other_user.near".to_string().try_into().unwrap())
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            contract
+                .get_tier_availability(&Epoch(1), &accounts(1))
+                .unwrap()[&UserNFTRank::Common],
+            99
+        );
+
+        // a failed refund transfer records the pledges back
+        testing_env!(
+            get_context(accounts(1), 0),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+
+        contract.on_external_refund_ft_tokens_callback(Epoch(1), accounts(1), vec![0, 1]);
+
+        assert_eq!(
+            contract
+                .user_funds
+                .get(&Epoch(1))
+                .unwrap()
+                .get(&user_id)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(
+            contract
+                .get_tier_availability(&Epoch(1), &accounts(1))
+                .unwrap()[&UserNFTRank::Common],
+            97
+        );
+    }
+
+    #[test]
+    fn test_claim_refund_fails_if_not_refundable() {
+        let mut contract = get_funded_contract();
+
+        testing_env!(get_context("user.near".to_string().try_into().unwrap(), 0));
+
+        assert!(matches!(
+            contract.claim_refund(Epoch(1), accounts(1)),
+            Err(MetaDaoError::FundsNotRefundable)
+        ));
+    }
+
+    #[test]
+    fn test_refund_backers_works() {
+        let mut contract = get_funded_contract();
+
+        contract.deregister_creator(accounts(1)).unwrap();
+
+        contract
+            .refund_backers(Epoch(1), accounts(1), Some(1), Some(2))
+            .unwrap();
+
+        assert_eq!(refunded_entries(&contract), vec![false, true, true, false]);
+
+        contract
+            .refund_backers(Epoch(1), accounts(1), None, None)
+            .unwrap();
+
+        assert_eq!(refunded_entries(&contract), vec![true, true, true, true]);
+    }
+
+    #[test]
+    fn test_claim_refund_works_if_funding_goal_not_met() {
+        let admin: AccountId = accounts(1);
+        testing_env!(get_context(admin.clone(), parse_near!("0.1 N")));
+
+        let mut contract = MetaDaoContract::new(admin);
+
+        let mut protocol_accounts_fees = HashMap::<FTAccountId, u16>::new();
+
+        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 500);
+
+        contract
+            .create_new_epoch(Some(protocol_accounts_fees), None)
+            .unwrap();
+
+        contract.set_registration().unwrap();
+        contract
+            .creator_registration(
+                get_allowed_registry_metadata(&contract),
+                Some(HashMap::from_iter([(
+                    "wrap.near".to_string().try_into().unwrap(),
+                    1_000_u128,
+                )])),
+            )
+            .unwrap();
+        contract.set_funding().unwrap();
+
+        let user_id: AccountId = "user.near".to_string().try_into().unwrap();
+
+        contract
+            .user_funding_creator(
+                user_id.clone(),
+                accounts(1),
+                UserNFTRank::Rare,
+                500_u128,
+                "wrap.near".to_string().try_into().unwrap(),
+            )
+            .unwrap();
+
+        // funding goal is only checked after the funding period
+        assert!(!contract.is_refundable(&Epoch(1), &accounts(1)));
+        assert!(matches!(
+            contract.mint_nfts_for_users(Epoch(1), accounts(1)),
+            Err(MetaDaoError::NotInMintingPeriod)
+        ));
+
+        contract.set_minting().unwrap();
+
+        assert!(contract.is_refundable(&Epoch(1), &accounts(1)));
+        assert!(matches!(
+            contract.mint_nfts_for_users(Epoch(1), accounts(1)),
+            Err(MetaDaoError::FundingGoalNotMet)
+        ));
+
+        testing_env!(get_context(user_id, 0));
+
+        contract.claim_refund(Epoch(1), accounts(1)).unwrap();
+
+        assert_eq!(refunded_entries(&contract), vec![true]);
+    }
+
+    #[test]
+    fn test_on_external_refund_ft_tokens_callback_restores_entries_on_failure() {
+        let mut contract = get_funded_contract();
+
+        contract.cancel_epoch().unwrap();
+
+        contract
+            .refund_backers(Epoch(1), accounts(1), None, None)
+            .unwrap();
+
+        // keeps the contract storage, unlike a fresh mocked blockchain
+        testing_env!(
+            get_context(accounts(1), 0),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+
+        contract.on_external_refund_ft_tokens_callback(Epoch(1), accounts(1), vec![0, 1]);
+
+        assert_eq!(refunded_entries(&contract), vec![false, false, true, true]);
+    }
+
+    #[test]
+    fn test_archive_epoch_waits_for_refund_callbacks() {
+        let mut contract = get_funded_contract();
+
+        contract.cancel_epoch().unwrap();
+        contract
+            .refund_backers(Epoch(1), accounts(1), None, None)
+            .unwrap();
+        assert_eq!(contract.pending_refunds.get(&Epoch(1)), Some(3));
+
+        // the refund transfers may still fail, and their entries be restored
+        assert!(matches!(
+            contract.archive_epoch(Epoch(1)),
+            Err(MetaDaoError::EpochCannotBeArchived)
+        ));
+
+        testing_env!(
+            get_context(accounts(1), 0),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Failed],
+        );
+        contract.on_external_refund_ft_tokens_callback(Epoch(1), accounts(1), vec![0, 1]);
+
+        testing_env!(
+            get_context(accounts(1), 0),
+            VMConfig::test(),
+            RuntimeFeesConfig::test(),
+            Default::default(),
+            vec![PromiseResult::Successful(vec![])],
+        );
+        contract.on_external_refund_ft_tokens_callback(Epoch(1), accounts(1), vec![2]);
+        contract.on_external_refund_ft_tokens_callback(Epoch(1), accounts(1), vec![3]);
+        assert!(contract.pending_refunds.get(&Epoch(1)).is_none());
+
+        // the restored entries are refunded again before archiving
+        assert!(matches!(
+            contract.archive_epoch(Epoch(1)),
+            Err(MetaDaoError::EpochCannotBeArchived)
+        ));
+
+        contract
+            .refund_backers(Epoch(1), accounts(1), None, None)
+            .unwrap();
+        contract.on_external_refund_ft_tokens_callback(Epoch(1), accounts(1), vec![0, 1]);
+
+        contract.archive_epoch(Epoch(1)).unwrap();
+    }
+}
@@ -1,112 +1,309 @@
+use std::collections::HashSet;
+use std::convert::TryFrom;
+
 use near_contract_standards::non_fungible_token::metadata::TokenMetadata;
 use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
 use near_sdk::json_types::Base64VecU8;
 use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{Promise, StorageUsage};
 
-use crate::consts::CREATOR_REGISTRY_STORAGE_COST;
+use crate::consts::FT_STORAGE_DEPOSIT;
+use crate::events::{CreatorDeregistrationLog, CreatorMetadataUpdateLog, CreatorRegistrationLog};
 use crate::{
-    error::MetaDaoError,
-    nft::{
-        CreatorNFTCopies, CreatorNFTDescription, CreatorNFTExtra, CreatorNFTMedia,
-        CreatorNFTRanking, CreatorNFTReference, CreatorNFTTitle,
+    consts::{
+        MAX_NFT_TEXT_LENGTH, MAX_NFT_TITLE_LENGTH, MAX_NFT_URL_LENGTH, NFT_RANKING, NFT_URL_SCHEMES,
     },
+    error::MetaDaoError,
     *,
 };
 
+/// [`TierConfig`] holds everything about one NFT tier of a creator: its prices, per fungible token,
+/// and the metadata of the NFTs minted for it
 #[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Debug, PartialEq)]
 #[serde(crate = "near_sdk::serde")]
+pub struct TierConfig {
+    pub nft_rank: UserNFTRank,
+    pub prices: HashMap<FTAccountId, u128>,
+    pub title: String,
+    pub description: String,
+    pub media: String,
+    pub copies: u64,
+    pub extra: String,
+    pub reference: Option<String>,
+}
+
+impl TierConfig {
+    pub fn get_price(&self, ft_token_id: &FTAccountId) -> Result<u128, MetaDaoError> {
+        self.prices
+            .get(ft_token_id)
+            .copied()
+            .ok_or(MetaDaoError::InvalidFTTokenId)
+    }
+}
+
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+#[serde(try_from = "CreatorMetadataInput")]
 pub struct CreatorMetadata {
-    nft_ranks: Vec<CreatorNFTRanking>,
-    titles: Vec<CreatorNFTTitle>,
-    descriptions: Vec<CreatorNFTDescription>,
-    medias: Vec<CreatorNFTMedia>,
-    copies: Vec<CreatorNFTCopies>,
-    extras: Vec<CreatorNFTExtra>,
-    references: Vec<CreatorNFTReference>,
+    tiers: Vec<TierConfig>,
 }
 
-impl CreatorMetadata {
-    pub fn new(
-        nft_ranks: Vec<CreatorNFTRanking>,
-        titles: Vec<CreatorNFTTitle>,
-        descriptions: Vec<CreatorNFTDescription>,
-        medias: Vec<CreatorNFTMedia>,
-        copies: Vec<CreatorNFTCopies>,
-        extras: Vec<CreatorNFTExtra>,
-        references: Vec<CreatorNFTReference>,
-    ) -> Self {
-        Self {
-            nft_ranks,
-            titles,
-            descriptions,
-            medias,
-            copies,
-            extras,
-            references,
+/// A value of a [`LegacyCreatorMetadata`] field, tagged with the NFT rank of the tier it belongs to
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub enum LegacyTierValue<T> {
+    Common(T),
+    Uncommon(T),
+    Rare(T),
+}
+
+impl<T> LegacyTierValue<T> {
+    pub fn nft_rank(&self) -> UserNFTRank {
+        match self {
+            Self::Common(_) => UserNFTRank::Common,
+            Self::Uncommon(_) => UserNFTRank::Uncommon,
+            Self::Rare(_) => UserNFTRank::Rare,
         }
     }
 
-    pub fn nft_rank(&self, user_nft_rank: UserNFTRank) -> CreatorNFTRanking {
-        match user_nft_rank {
-            UserNFTRank::Common => self.nft_ranks[0].clone(),
-            UserNFTRank::Uncommon => self.nft_ranks[1].clone(),
-            UserNFTRank::Rare => self.nft_ranks[2].clone(),
+    pub fn value(&self) -> &T {
+        match self {
+            Self::Common(value) | Self::Uncommon(value) | Self::Rare(value) => value,
         }
     }
+}
 
-    pub fn get_nft_ranks(&self) -> Vec<CreatorNFTRanking> {
-        self.nft_ranks.clone()
-    }
+/// The layout of [`CreatorMetadata`] before tiers were grouped into [`TierConfig`] records,
+/// i.e. one vector per field, each with an entry per tier. It is still accepted as JSON input,
+/// and kept in storage for the creators registered before the upgrade, until migrated by
+/// [`MetaDaoContract::migrate_legacy_creators`]
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Clone, Debug, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct LegacyCreatorMetadata {
+    pub nft_ranks: Vec<LegacyTierValue<HashMap<FTAccountId, u128>>>,
+    pub titles: Vec<LegacyTierValue<String>>,
+    pub descriptions: Vec<LegacyTierValue<String>>,
+    pub medias: Vec<LegacyTierValue<String>>,
+    pub copies: Vec<LegacyTierValue<u64>>,
+    pub extras: Vec<LegacyTierValue<String>>,
+    pub references: Vec<LegacyTierValue<Option<String>>>,
+}
 
-    pub fn get_titles(&self) -> Vec<CreatorNFTTitle> {
-        self.titles.clone()
-    }
+/// The JSON input of [`CreatorMetadata`], either per-tier records or the legacy per-field vectors.
+/// Fields are listed explicitly, rather than as an untagged enum, which cannot buffer `u128` prices
+#[derive(Deserialize)]
+#[serde(crate = "near_sdk::serde")]
+struct CreatorMetadataInput {
+    tiers: Option<Vec<TierConfig>>,
+    nft_ranks: Option<Vec<LegacyTierValue<HashMap<FTAccountId, u128>>>>,
+    titles: Option<Vec<LegacyTierValue<String>>>,
+    descriptions: Option<Vec<LegacyTierValue<String>>>,
+    medias: Option<Vec<LegacyTierValue<String>>>,
+    copies: Option<Vec<LegacyTierValue<u64>>>,
+    extras: Option<Vec<LegacyTierValue<String>>>,
+    references: Option<Vec<LegacyTierValue<Option<String>>>>,
+}
 
-    pub fn get_descriptions(&self) -> Vec<CreatorNFTDescription> {
-        self.descriptions.clone()
+impl TryFrom<CreatorMetadataInput> for CreatorMetadata {
+    type Error = MetaDaoError;
+
+    fn try_from(input: CreatorMetadataInput) -> Result<Self, Self::Error> {
+        match input {
+            CreatorMetadataInput {
+                tiers: Some(tiers),
+                nft_ranks: None,
+                titles: None,
+                descriptions: None,
+                medias: None,
+                copies: None,
+                extras: None,
+                references: None,
+            } => Ok(Self::new(tiers)),
+            CreatorMetadataInput {
+                tiers: None,
+                nft_ranks: Some(nft_ranks),
+                titles: Some(titles),
+                descriptions: Some(descriptions),
+                medias: Some(medias),
+                copies: Some(copies),
+                extras: Some(extras),
+                references: Some(references),
+            } => Self::try_from(LegacyCreatorMetadata {
+                nft_ranks,
+                titles,
+                descriptions,
+                medias,
+                copies,
+                extras,
+                references,
+            }),
+            _ => Err(MetaDaoError::InvalidCreatorMetadataTiers),
+        }
     }
+}
 
-    pub fn get_medias(&self) -> Vec<CreatorNFTMedia> {
-        self.medias.clone()
+impl TryFrom<LegacyCreatorMetadata> for CreatorMetadata {
+    type Error = MetaDaoError;
+
+    /// Groups the entries of each field by tier. Every field needs one entry per tier,
+    /// with the same NFT rank at the same position
+    fn try_from(legacy: LegacyCreatorMetadata) -> Result<Self, Self::Error> {
+        let tiers_count = legacy.nft_ranks.len();
+
+        if [
+            legacy.titles.len(),
+            legacy.descriptions.len(),
+            legacy.medias.len(),
+            legacy.copies.len(),
+            legacy.extras.len(),
+            legacy.references.len(),
+        ]
+        .iter()
+        .any(|len| *len != tiers_count)
+        {
+            return Err(MetaDaoError::InvalidCreatorMetadataTiers);
+        }
+
+        let mut tiers = Vec::with_capacity(tiers_count);
+
+        for index in 0..tiers_count {
+            let nft_rank = legacy.nft_ranks[index].nft_rank();
+
+            if [
+                legacy.titles[index].nft_rank(),
+                legacy.descriptions[index].nft_rank(),
+                legacy.medias[index].nft_rank(),
+                legacy.copies[index].nft_rank(),
+                legacy.extras[index].nft_rank(),
+                legacy.references[index].nft_rank(),
+            ]
+            .iter()
+            .any(|field_nft_rank| *field_nft_rank != nft_rank)
+            {
+                return Err(MetaDaoError::InvalidCreatorMetadataTiers);
+            }
+
+            tiers.push(TierConfig {
+                nft_rank,
+                prices: legacy.nft_ranks[index].value().clone(),
+                title: legacy.titles[index].value().clone(),
+                description: legacy.descriptions[index].value().clone(),
+                media: legacy.medias[index].value().clone(),
+                copies: *legacy.copies[index].value(),
+                extra: legacy.extras[index].value().clone(),
+                reference: legacy.references[index].value().clone(),
+            });
+        }
+
+        Ok(Self::new(tiers))
     }
+}
 
-    pub fn get_copies(&self) -> Vec<CreatorNFTCopies> {
-        self.copies.clone()
+impl CreatorMetadata {
+    pub fn new(tiers: Vec<TierConfig>) -> Self {
+        Self { tiers }
     }
 
-    pub fn get_extras(&self) -> Vec<CreatorNFTExtra> {
-        self.extras.clone()
+    pub fn get_tiers(&self) -> &[TierConfig] {
+        &self.tiers
     }
 
-    pub fn get_references(&self) -> Vec<CreatorNFTReference> {
-        self.references.clone()
+    pub fn tier(&self, nft_rank: &UserNFTRank) -> Result<&TierConfig, MetaDaoError> {
+        self.tiers
+            .iter()
+            .find(|tier| tier.nft_rank == *nft_rank)
+            .ok_or(MetaDaoError::InvalidCreatorMetadataTiers)
     }
 
-    pub fn get_token_metadata(&self, nft_rank: UserNFTRank) -> Result<TokenMetadata, MetaDaoError> {
-        let index = match nft_rank {
-            UserNFTRank::Common => 0_usize,
-            UserNFTRank::Uncommon => 1_usize,
-            UserNFTRank::Rare => 2_usize,
-        };
+    /// Checks there are exactly [`NFT_RANKING`] tiers, in Common, Uncommon and Rare order,
+    /// with non-empty titles, positive copies, valid URLs within the length limits, and positive
+    /// prices in `allowed_tokens` only
+    pub fn validate(
+        &self,
+        allowed_tokens: &UnorderedMap<FTAccountId, u16>,
+    ) -> Result<(), MetaDaoError> {
+        let has_nft_tiers = self.tiers.len() == NFT_RANKING
+            && self.tiers.iter().map(|tier| &tier.nft_rank).eq([
+                UserNFTRank::Common,
+                UserNFTRank::Uncommon,
+                UserNFTRank::Rare,
+            ]
+            .iter());
+
+        if !has_nft_tiers {
+            return Err(MetaDaoError::InvalidCreatorMetadataTiers);
+        }
 
-        let title = Some(String::from(self.get_titles()[index].get_title()));
-        let description = Some(String::from(
-            self.get_descriptions()[index].get_description(),
-        ));
-        let media = Some(String::from(self.get_medias()[index].get_media()));
-        let media_hash = env::sha256(media.clone().unwrap().as_bytes());
+        for tier in self.tiers.iter() {
+            if tier.prices.is_empty() || tier.prices.values().any(|amount| *amount == 0) {
+                return Err(MetaDaoError::InvalidNFTPrice);
+            }
+
+            if tier
+                .prices
+                .keys()
+                .any(|ft_token_id| allowed_tokens.get(ft_token_id).is_none())
+            {
+                return Err(MetaDaoError::InvalidFTTokenId);
+            }
+
+            if tier.title.trim().is_empty() {
+                return Err(MetaDaoError::EmptyNFTTitle);
+            }
+
+            if tier.copies == 0 {
+                return Err(MetaDaoError::InvalidNFTCopies);
+            }
+
+            if tier.title.len() > MAX_NFT_TITLE_LENGTH
+                || tier.description.len() > MAX_NFT_TEXT_LENGTH
+                || tier.extra.len() > MAX_NFT_TEXT_LENGTH
+            {
+                return Err(MetaDaoError::NFTMetadataFieldTooLong);
+            }
+
+            for url in std::iter::once(&tier.media).chain(tier.reference.iter()) {
+                if url.len() > MAX_NFT_URL_LENGTH {
+                    return Err(MetaDaoError::NFTMetadataFieldTooLong);
+                }
+
+                if !is_valid_nft_url(url) {
+                    return Err(MetaDaoError::InvalidNFTUrl);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the metadata of the NFT of `edition` within `nft_rank`. The edition is appended
+    /// to the title, e.g. "title (7 of 50)", and recorded in `extra`, along with the tier extra
+    pub fn get_token_metadata(
+        &self,
+        nft_rank: UserNFTRank,
+        edition: u64,
+    ) -> Result<TokenMetadata, MetaDaoError> {
+        let tier = self.tier(&nft_rank)?;
+
+        let title = Some(format!("{} ({} of {})", tier.title, edition, tier.copies));
+        let description = Some(tier.description.clone());
+        let media = Some(tier.media.clone());
+        let media_hash = env::sha256(tier.media.as_bytes());
         let media_hash = Some(Base64VecU8::from(media_hash));
-        let copies = Some(self.get_copies()[index].get_copies());
+        let copies = Some(tier.copies);
         let issued_at = Some(format!("block_timestamp: {}", env::block_timestamp()));
-        let extra = Some(String::from(self.get_extras()[index].get_extra()));
-        let reference = self.get_references()[index].get_reference();
-        let reference_hash = if reference.is_none() {
-            Some(Base64VecU8::from(env::sha256(
-                reference.clone().unwrap().as_bytes(),
-            )))
-        } else {
-            None
-        };
+        let extra = Some(
+            near_sdk::serde_json::json!({
+                "edition": edition,
+                "copies": tier.copies,
+                "extra": tier.extra,
+            })
+            .to_string(),
+        );
+        let reference = tier.reference.clone();
+        let reference_hash = reference
+            .as_ref()
+            .map(|reference| Base64VecU8::from(env::sha256(reference.as_bytes())));
 
         let token_metadata = TokenMetadata {
             title,
@@ -127,27 +324,226 @@ impl CreatorMetadata {
     }
 }
 
+/// Checks `url` has one of the [`NFT_URL_SCHEMES`], a non-empty location and no whitespace
+fn is_valid_nft_url(url: &str) -> bool {
+    NFT_URL_SCHEMES.iter().any(|scheme| {
+        url.strip_prefix(scheme)
+            .map(|location| !location.is_empty())
+            .unwrap_or(false)
+    }) && !url.chars().any(char::is_whitespace)
+}
+
+impl MetaDaoContract {
+    /// Returns the funding goal of `creator_account_id` for `epoch`, if any
+    pub fn get_funding_goal(
+        &self,
+        epoch: &Epoch,
+        creator_account_id: &CreatorAccountId,
+    ) -> Option<HashMap<FTAccountId, u128>> {
+        self.creators_funding_goals
+            .get(epoch)
+            .and_then(|goals| goals.get(creator_account_id))
+    }
+
+    /// Checks if `creator_account_id` obtained, for each fungible token of its funding goal,
+    /// at least the goal amount. Creators without a funding goal always meet it
+    pub fn is_funding_goal_met(
+        &self,
+        epoch: &Epoch,
+        creator_account_id: &CreatorAccountId,
+    ) -> bool {
+        let funding_goal = match self.get_funding_goal(epoch, creator_account_id) {
+            Some(funding_goal) => funding_goal,
+            None => return true,
+        };
+
+        let creator_funding = self
+            .creator_funding
+            .get(epoch)
+            .and_then(|fundings| fundings.get(creator_account_id))
+            .unwrap_or_default();
+
+        funding_goal.iter().all(|(ft_token_id, goal_amount)| {
+            let obtained_amount = creator_funding
+                .iter()
+                .filter(|ot| ot.ft_token_id == *ft_token_id && !ot.already_refunded)
+                .fold(0u128, |a, ot| a + ot.amount);
+
+            obtained_amount >= *goal_amount
+        })
+    }
+
+    /// Returns the copies of each NFT rank of `creator_account_id` left to pledge for, in `epoch`
+    pub fn get_tier_availability(
+        &self,
+        epoch: &Epoch,
+        creator_account_id: &CreatorAccountId,
+    ) -> Result<HashMap<UserNFTRank, u64>, MetaDaoError> {
+        let creator_metadata = self
+            .creators_metadata
+            .get(epoch)
+            .ok_or(MetaDaoError::InvalidCurrentEpoch)?
+            .get(creator_account_id)
+            .ok_or(MetaDaoError::CreatorIsNotRegistered)?;
+
+        let sold_copies = self
+            .tier_sold_copies
+            .get(epoch)
+            .and_then(|tier_sold_copies| tier_sold_copies.get(creator_account_id))
+            .unwrap_or_default();
+
+        Ok(creator_metadata
+            .get_tiers()
+            .iter()
+            .map(|tier| {
+                let sold = sold_copies.get(&tier.nft_rank).copied().unwrap_or(0);
+                (tier.nft_rank.clone(), tier.copies.saturating_sub(sold))
+            })
+            .collect())
+    }
+
+    /// Tops up the fungible token storage reserve of `creator_account_id`, so that it covers one
+    /// `storage_deposit` per fungible token its tiers are priced in. Returns the amount added
+    fn reserve_ft_storage(
+        &mut self,
+        creator_account_id: &CreatorAccountId,
+        metadata: &CreatorMetadata,
+    ) -> Balance {
+        let ft_token_ids: HashSet<&FTAccountId> = metadata
+            .get_tiers()
+            .iter()
+            .flat_map(|tier| tier.prices.keys())
+            .filter(|ft_token_id| **ft_token_id != near_token_id())
+            .collect();
+
+        let required = ft_token_ids.len() as Balance * FT_STORAGE_DEPOSIT;
+        let reserve = self
+            .creator_ft_storage_reserves
+            .get(creator_account_id)
+            .unwrap_or(0);
+
+        if reserve >= required {
+            return 0;
+        }
+
+        self.creator_ft_storage_reserves
+            .insert(creator_account_id, &required);
+
+        required - reserve
+    }
+
+    fn assert_creator_metadata_size(&self, metadata: &CreatorMetadata) -> Result<(), MetaDaoError> {
+        let metadata_size = metadata
+            .try_to_vec()
+            .map_err(|_| MetaDaoError::CreatorMetadataTooLarge)?
+            .len() as StorageUsage;
+
+        if metadata_size > self.max_creator_metadata_size {
+            return Err(MetaDaoError::CreatorMetadataTooLarge);
+        }
+
+        Ok(())
+    }
+
+    /// Creators can only change their registration while the current epoch is in registration
+    fn assert_creator_can_change_registration(
+        &self,
+        creator_account_id: &CreatorAccountId,
+    ) -> Result<(), MetaDaoError> {
+        if !self.is_epoch_on() {
+            return Err(MetaDaoError::EpochIsOff);
+        }
+
+        if !self.is_in_registration() {
+            return Err(MetaDaoError::NotInRegistrationPeriod);
+        }
+
+        let is_registered = self
+            .creators_per_epoch_set
+            .get(&self.epoch)
+            .map(|creators| creators.contains(creator_account_id))
+            .unwrap_or(false);
+
+        if !is_registered {
+            return Err(MetaDaoError::CreatorIsNotRegistered);
+        }
+
+        Ok(())
+    }
+
+    /// Charges `account_id` the bytes used since `initial_storage_usage`, along with `reserved`,
+    /// out of the attached deposit, and refunds the rest of it, along with the cost of the bytes
+    /// released, if any
+    pub fn settle_attached_storage(
+        &self,
+        account_id: &AccountId,
+        initial_storage_usage: StorageUsage,
+        reserved: Balance,
+    ) -> Result<(), MetaDaoError> {
+        let storage_usage = env::storage_usage();
+        let attached_deposit = env::attached_deposit()
+            .checked_sub(reserved)
+            .ok_or(MetaDaoError::UncoveredStorageCosts)?;
+
+        let refund = if storage_usage >= initial_storage_usage {
+            let storage_cost =
+                (storage_usage - initial_storage_usage) as u128 * env::STORAGE_PRICE_PER_BYTE;
+
+            if attached_deposit < storage_cost {
+                return Err(MetaDaoError::UncoveredStorageCosts);
+            }
+
+            attached_deposit - storage_cost
+        } else {
+            attached_deposit
+                + (initial_storage_usage - storage_usage) as u128 * env::STORAGE_PRICE_PER_BYTE
+        };
+
+        if refund > 0 {
+            Promise::new(account_id.clone()).transfer(refund);
+        }
+
+        Ok(())
+    }
+}
+
 #[near_bindgen]
 impl MetaDaoContract {
     #[payable]
     #[handle_result]
-    pub fn creator_registration(&mut self, metadata: CreatorMetadata) -> Result<(), MetaDaoError> {
+    pub fn creator_registration(
+        &mut self,
+        metadata: CreatorMetadata,
+        funding_goal: Option<HashMap<FTAccountId, u128>>,
+    ) -> Result<(), MetaDaoError> {
         let creator_account_id = env::predecessor_account_id();
 
-        if env::attached_deposit()
-            < (CREATOR_REGISTRY_STORAGE_COST as u128) * env::STORAGE_PRICE_PER_BYTE
-        {
-            return Err(MetaDaoError::UncoveredStorageCosts);
-        }
-
-        if !self.is_epoch_on {
+        if !self.is_epoch_on() {
             return Err(MetaDaoError::EpochIsOff);
         }
 
-        if !self.in_registration {
+        if !self.is_in_registration() {
             return Err(MetaDaoError::NotInRegistrationPeriod);
         }
 
+        if let Some(funding_goal) = &funding_goal {
+            if funding_goal.is_empty() || funding_goal.values().any(|amount| *amount == 0) {
+                return Err(MetaDaoError::InvalidFundingGoal);
+            }
+
+            if funding_goal
+                .keys()
+                .any(|ft_token_id| self.protocol_allowed_tokens_fees.get(ft_token_id).is_none())
+            {
+                return Err(MetaDaoError::InvalidFTTokenId);
+            }
+        }
+
+        self.assert_creator_metadata_size(&metadata)?;
+        metadata.validate(&self.protocol_allowed_tokens_fees)?;
+
+        let initial_storage_usage = env::storage_usage();
+
         let mut creator_funding = self
             .creator_funding
             .get(&self.epoch)
@@ -163,6 +559,10 @@ impl MetaDaoContract {
             .get(&self.epoch)
             .ok_or(MetaDaoError::EpochIsOff)?;
 
+        if creators_per_epoch.contains(&creator_account_id) {
+            return Err(MetaDaoError::CreatorAlreadyRegistered);
+        }
+
         creator_funding.insert(&creator_account_id, &vec![]);
         creators_metadata.insert(&creator_account_id, &metadata);
         creators_per_epoch.insert(&creator_account_id);
@@ -173,141 +573,193 @@ impl MetaDaoContract {
         self.creators_per_epoch_set
             .insert(&self.epoch, &creators_per_epoch);
 
-        Ok(())
+        MetaStageEvent::CreatorRegistration(vec![CreatorRegistrationLog {
+            epoch: self.epoch.count(),
+            creator_id: creator_account_id.clone(),
+            funding_goal: funding_goal.as_ref().map(|goal| {
+                goal.iter()
+                    .map(|(ft_token_id, amount)| (ft_token_id.clone(), U128(*amount)))
+                    .collect()
+            }),
+        }])
+        .emit();
+
+        if let Some(funding_goal) = funding_goal {
+            let mut creators_funding_goals = self
+                .creators_funding_goals
+                .get(&self.epoch)
+                .ok_or(MetaDaoError::EpochIsOff)?;
+
+            creators_funding_goals.insert(&creator_account_id, &funding_goal);
+            self.creators_funding_goals
+                .insert(&self.epoch, &creators_funding_goals);
+        }
+
+        // the attached deposit also covers the creator storage registration on fungible token
+        // contracts, for when it withdraws
+        let reserved = self.reserve_ft_storage(&creator_account_id, &metadata);
+
+        self.settle_attached_storage(&creator_account_id, initial_storage_usage, reserved)
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::collections::HashMap;
+    /// Replaces the metadata of the calling creator, while the current epoch is in registration.
+    /// Additional bytes have to be covered by the attached deposit, released bytes are refunded
+    #[payable]
+    #[handle_result]
+    pub fn update_creator_metadata(
+        &mut self,
+        metadata: CreatorMetadata,
+    ) -> Result<(), MetaDaoError> {
+        let creator_account_id = env::predecessor_account_id();
 
-    use near_sdk::test_utils::{accounts, VMContextBuilder};
-    use near_sdk::{
-        testing_env, AccountId, Gas, MockedBlockchain, PromiseResult, RuntimeFeesConfig, VMConfig,
-        VMContext,
-    };
-    use std::convert::TryInto;
+        self.assert_creator_can_change_registration(&creator_account_id)?;
+        self.assert_creator_metadata_size(&metadata)?;
+        metadata.validate(&self.protocol_allowed_tokens_fees)?;
 
-    /// utility function for testing callbacks logic
-    #[allow(dead_code)]
-    pub fn testing_env_with_promise_results(
-        context: VMContext,
-        promise_results: Vec<PromiseResult>,
-    ) {
-        near_sdk::env::set_blockchain_interface(MockedBlockchain::new(
-            context,
-            VMConfig::test(),
-            RuntimeFeesConfig::test(),
-            promise_results,
-            Default::default(),
-            Default::default(),
-            None,
-        ));
+        let initial_storage_usage = env::storage_usage();
+
+        let mut creators_metadata = self
+            .creators_metadata
+            .get(&self.epoch)
+            .ok_or(MetaDaoError::EpochIsOff)?;
+
+        creators_metadata.insert(&creator_account_id, &metadata);
+        self.creators_metadata
+            .insert(&self.epoch, &creators_metadata);
+
+        MetaStageEvent::CreatorMetadataUpdate(vec![CreatorMetadataUpdateLog {
+            epoch: self.epoch.count(),
+            creator_id: creator_account_id.clone(),
+        }])
+        .emit();
+
+        let reserved = self.reserve_ft_storage(&creator_account_id, &metadata);
+
+        self.settle_attached_storage(&creator_account_id, initial_storage_usage, reserved)
     }
 
-    pub fn to_yocto(value: &str) -> u128 {
-        let vals: Vec<_> = value.split('.').collect();
-        let part1 = vals[0].parse::<u128>().unwrap() * 10u128.pow(24);
-        if vals.len() > 1 {
-            let power = vals[1].len() as u32;
-            let part2 = vals[1].parse::<u128>().unwrap() * 10u128.pow(24 - power);
-            part1 + part2
-        } else {
-            part1
-        }
+    /// Removes the calling creator from the current epoch, while it is in registration,
+    /// and refunds the storage its registration used, along with its fungible token storage
+    /// reserve, unless it has fungible tokens left to withdraw
+    #[handle_result]
+    pub fn withdraw_registration(&mut self) -> Result<(), MetaDaoError> {
+        let creator_account_id = env::predecessor_account_id();
+
+        self.assert_creator_can_change_registration(&creator_account_id)?;
+
+        let initial_storage_usage = env::storage_usage();
+
+        let mut creator_funding = self
+            .creator_funding
+            .get(&self.epoch)
+            .ok_or(MetaDaoError::EpochIsOff)?;
+
+        let mut creators_metadata = self
+            .creators_metadata
+            .get(&self.epoch)
+            .ok_or(MetaDaoError::EpochIsOff)?;
+
+        let mut creators_per_epoch = self
+            .creators_per_epoch_set
+            .get(&self.epoch)
+            .ok_or(MetaDaoError::EpochIsOff)?;
+
+        let mut creators_funding_goals = self
+            .creators_funding_goals
+            .get(&self.epoch)
+            .ok_or(MetaDaoError::EpochIsOff)?;
+
+        creator_funding.remove(&creator_account_id);
+        creators_metadata.remove(&creator_account_id);
+        creators_per_epoch.remove(&creator_account_id);
+        creators_funding_goals.remove(&creator_account_id);
+
+        self.creator_funding.insert(&self.epoch, &creator_funding);
+        self.creators_metadata
+            .insert(&self.epoch, &creators_metadata);
+        self.creators_per_epoch_set
+            .insert(&self.epoch, &creators_per_epoch);
+        self.creators_funding_goals
+            .insert(&self.epoch, &creators_funding_goals);
+
+        MetaStageEvent::CreatorRegistrationWithdrawal(vec![CreatorDeregistrationLog {
+            epoch: self.epoch.count(),
+            creator_id: creator_account_id.clone(),
+        }])
+        .emit();
+
+        self.release_ft_storage_reserve(&creator_account_id);
+
+        self.settle_attached_storage(&creator_account_id, initial_storage_usage, 0)
     }
 
-    fn get_context_with_storage(storage: u128) -> VMContext {
-        let contract_account_id: AccountId = "conliq.testnet".to_string().try_into().unwrap();
+    /// Sets the maximum size, in bytes, of the serialized metadata of a creator registration
+    #[handle_result]
+    pub fn set_max_creator_metadata_size(
+        &mut self,
+        max_creator_metadata_size: StorageUsage,
+    ) -> Result<(), MetaDaoError> {
+        if env::predecessor_account_id() != self.admin {
+            return Err(MetaDaoError::InvalidAdminCall);
+        }
+
+        self.max_creator_metadata_size = max_creator_metadata_size;
 
-        VMContextBuilder::new()
-            .current_account_id(contract_account_id)
-            .attached_deposit(to_yocto("1000"))
-            .signer_account_id(accounts(0))
-            .predecessor_account_id(accounts(0))
-            .prepaid_gas(Gas(300 * 10u64.pow(16)))
-            .attached_deposit(storage)
-            .build()
+        Ok(())
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{get_context, get_legacy_registry_metadata};
+    use std::collections::HashMap;
+
+    use near_sdk::mock::VmAction;
+    use near_sdk::test_utils::{accounts, get_created_receipts};
+    use near_sdk::{testing_env, AccountId};
+    use std::convert::TryInto;
 
     fn get_registry_metadata() -> CreatorMetadata {
-        CreatorMetadata {
-            nft_ranks: vec![
-                CreatorNFTRanking::Common(HashMap::<FTAccountId, u128>::from_iter([(
-                    "ft_account_id.near".to_string().try_into().unwrap(),
-                    100_u128,
-                )])),
-                CreatorNFTRanking::Uncommon(HashMap::<FTAccountId, u128>::from_iter([(
-                    "ft_account_id.near".to_string().try_into().unwrap(),
-                    250_u128,
-                )])),
-                CreatorNFTRanking::Rare(HashMap::<FTAccountId, u128>::from_iter([(
-                    "ft_account_id.near".to_string().try_into().unwrap(),
-                    500_u128,
-                )])),
-            ],
-            titles: vec![
-                CreatorNFTTitle::Common("common".to_string()),
-                CreatorNFTTitle::Uncommon("uncommon".to_string()),
-                CreatorNFTTitle::Rare("rare".to_string()),
-            ],
-            descriptions: vec![
-                CreatorNFTDescription::Common("common".to_string()),
-                CreatorNFTDescription::Uncommon("uncommon".to_string()),
-                CreatorNFTDescription::Rare("rare".to_string()),
-            ],
-            medias: vec![
-                CreatorNFTMedia::Common("media_common".to_string()),
-                CreatorNFTMedia::Uncommon("media_uncommon".to_string()),
-                CreatorNFTMedia::Rare("media_rare".to_string()),
-            ],
-            copies: vec![
-                CreatorNFTCopies::Common(100_u64),
-                CreatorNFTCopies::Uncommon(50_u64),
-                CreatorNFTCopies::Rare(5_u64),
-            ],
-            extras: vec![
-                CreatorNFTExtra::Common("extra_common".to_string()),
-                CreatorNFTExtra::Uncommon("extra_uncommon".to_string()),
-                CreatorNFTExtra::Rare("extra_rare".to_string()),
-            ],
-            references: vec![
-                CreatorNFTReference::Common(None),
-                CreatorNFTReference::Uncommon(None),
-                CreatorNFTReference::Rare(None),
-            ],
-        }
+        CreatorMetadata::try_from(get_legacy_registry_metadata()).unwrap()
     }
 
     #[test]
     fn it_works_registry() {
         let admin: AccountId = accounts(0);
-        let storage = (CREATOR_REGISTRY_STORAGE_COST as u128) * env::STORAGE_PRICE_PER_BYTE;
+        let storage = parse_near!("0.1 N");
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(0), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        let mut protocol_accounts_fees = HashMap::<FTAccountId, f64>::new();
+        let mut protocol_accounts_fees = HashMap::<FTAccountId, u16>::new();
 
-        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 0.05);
-        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 0.03);
+        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 500);
+        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 300);
 
         contract
-            .create_new_epoch(Some(protocol_accounts_fees))
+            .create_new_epoch(Some(protocol_accounts_fees), None)
             .unwrap();
 
-        contract.is_epoch_on = true;
-
         contract.set_registration().unwrap();
 
         let creator_account_id = accounts(0);
 
         let metadata = get_registry_metadata();
 
-        contract.creator_registration(metadata.clone()).unwrap();
+        contract
+            .creator_registration(metadata.clone(), None)
+            .unwrap();
+
+        // one fungible token storage registration is reserved, for `ft_account_id.near`
+        assert_eq!(
+            contract
+                .creator_ft_storage_reserves
+                .get(&creator_account_id),
+            Some(FT_STORAGE_DEPOSIT)
+        );
 
         let creator_funding = contract
             .creator_funding
@@ -339,66 +791,181 @@ mod tests {
     #[test]
     fn it_fails_creator_registry_if_not_enough_funds_for_storage() {
         let admin: AccountId = accounts(0);
-        let storage = (CREATOR_REGISTRY_STORAGE_COST as u128) * env::STORAGE_PRICE_PER_BYTE - 1u128;
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(0), parse_near!("0.001 N"));
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        let mut protocol_accounts_fees = HashMap::<FTAccountId, f64>::new();
+        let mut protocol_accounts_fees = HashMap::<FTAccountId, u16>::new();
 
-        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 0.05);
-        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 0.03);
+        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 500);
+        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 300);
 
         contract
-            .create_new_epoch(Some(protocol_accounts_fees))
+            .create_new_epoch(Some(protocol_accounts_fees), None)
             .unwrap();
 
-        contract.is_epoch_on = true;
-
         contract.set_registration().unwrap();
 
         let metadata = get_registry_metadata();
 
-        contract.is_epoch_on = false;
-
         assert!(contract
-            .creator_registration(metadata.clone())
+            .creator_registration(metadata.clone(), None)
             .unwrap_err()
             .to_string()
             .contains("Uncovered storage costs"));
     }
 
     #[test]
-    fn it_fails_creator_registry_if_epoch_is_on() {
+    fn it_refunds_creator_registry_storage_deposit_excess() {
+        let admin: AccountId = accounts(0);
+        let storage = parse_near!("0.1 N");
+
+        let context = get_context(accounts(0), storage);
+        testing_env!(context);
+
+        let mut contract = MetaDaoContract::new(admin.clone());
+
+        let protocol_accounts_fees = HashMap::<FTAccountId, u16>::from_iter([(
+            "wrap.near".to_string().try_into().unwrap(),
+            500,
+        )]);
+
+        contract
+            .create_new_epoch(Some(protocol_accounts_fees), None)
+            .unwrap();
+        contract.set_registration().unwrap();
+
+        let initial_storage_usage = env::storage_usage();
+
+        contract
+            .creator_registration(get_registry_metadata(), None)
+            .unwrap();
+
+        let storage_cost =
+            (env::storage_usage() - initial_storage_usage) as u128 * env::STORAGE_PRICE_PER_BYTE;
+
+        // one fungible token storage registration is reserved, for `wrap.near`
+        let ft_storage_reserve = FT_STORAGE_DEPOSIT;
+        assert_eq!(
+            contract.creator_ft_storage_reserves.get(&accounts(0)),
+            Some(ft_storage_reserve)
+        );
+
+        let refunds: Vec<_> = get_created_receipts()
+            .into_iter()
+            .filter(|receipt| receipt.receiver_id == accounts(0))
+            .flat_map(|receipt| receipt.actions)
+            .collect();
+
+        assert_eq!(
+            refunds,
+            vec![VmAction::Transfer {
+                deposit: storage - storage_cost - ft_storage_reserve
+            }]
+        );
+
+        // the reserve is only topped up on metadata updates
+        testing_env!(get_context(accounts(0), 0));
+        contract
+            .update_creator_metadata(get_registry_metadata())
+            .unwrap();
+        assert_eq!(
+            contract.creator_ft_storage_reserves.get(&accounts(0)),
+            Some(ft_storage_reserve)
+        );
+
+        // and it is sent back once the registration is withdrawn, to be reserved again
+        contract.withdraw_registration().unwrap();
+        assert!(contract
+            .creator_ft_storage_reserves
+            .get(&accounts(0))
+            .is_none());
+
+        testing_env!(get_context(accounts(0), FT_STORAGE_DEPOSIT - 1));
+        assert!(matches!(
+            contract.creator_registration(get_registry_metadata(), None),
+            Err(MetaDaoError::UncoveredStorageCosts)
+        ));
+    }
+
+    #[test]
+    fn it_fails_creator_registry_if_metadata_is_too_large() {
         let admin: AccountId = accounts(0);
-        let storage = (CREATOR_REGISTRY_STORAGE_COST as u128) * env::STORAGE_PRICE_PER_BYTE;
+        let storage = parse_near!("0.1 N");
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(0), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        let mut protocol_accounts_fees = HashMap::<FTAccountId, f64>::new();
+        let protocol_accounts_fees = HashMap::<FTAccountId, u16>::from_iter([(
+            "wrap.near".to_string().try_into().unwrap(),
+            500,
+        )]);
+
+        contract
+            .create_new_epoch(Some(protocol_accounts_fees), None)
+            .unwrap();
+        contract.set_registration().unwrap();
+
+        let metadata = get_registry_metadata();
+        let metadata_size = metadata.try_to_vec().unwrap().len() as StorageUsage;
 
-        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 0.05);
-        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 0.03);
+        contract
+            .set_max_creator_metadata_size(metadata_size - 1)
+            .unwrap();
+
+        assert!(matches!(
+            contract.creator_registration(metadata.clone(), None),
+            Err(MetaDaoError::CreatorMetadataTooLarge)
+        ));
 
         contract
-            .create_new_epoch(Some(protocol_accounts_fees))
+            .set_max_creator_metadata_size(metadata_size)
             .unwrap();
 
-        contract.is_epoch_on = true;
+        assert!(contract.creator_registration(metadata, None).is_ok());
+
+        testing_env!(get_context(accounts(0), 0));
+        contract.admin = accounts(1);
+
+        assert!(matches!(
+            contract.set_max_creator_metadata_size(0),
+            Err(MetaDaoError::InvalidAdminCall)
+        ));
+    }
+
+    #[test]
+    fn it_fails_creator_registry_if_epoch_is_on() {
+        let admin: AccountId = accounts(0);
+        let storage = parse_near!("0.1 N");
+
+        let context = get_context(accounts(0), storage);
+        testing_env!(context);
+
+        let mut contract = MetaDaoContract::new(admin.clone());
+
+        let mut protocol_accounts_fees = HashMap::<FTAccountId, u16>::new();
+
+        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 500);
+        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 300);
+
+        contract
+            .create_new_epoch(Some(protocol_accounts_fees), None)
+            .unwrap();
 
         contract.set_registration().unwrap();
 
         let metadata = get_registry_metadata();
 
-        contract.is_epoch_on = false;
+        contract
+            .epoch_phases
+            .insert(&contract.epoch, &EpochPhase::Cancelled);
 
         assert!(contract
-            .creator_registration(metadata.clone())
+            .creator_registration(metadata.clone(), None)
             .unwrap_err()
             .to_string()
             .contains("Currently, epoch is off"));
@@ -407,30 +974,385 @@ mod tests {
     #[test]
     fn it_fails_creator_registry_if_not_in_registration() {
         let admin: AccountId = accounts(0);
-        let storage = (CREATOR_REGISTRY_STORAGE_COST as u128) * env::STORAGE_PRICE_PER_BYTE;
+        let storage = parse_near!("0.1 N");
 
-        let context = get_context_with_storage(storage);
+        let context = get_context(accounts(0), storage);
         testing_env!(context);
 
         let mut contract = MetaDaoContract::new(admin.clone());
 
-        let mut protocol_accounts_fees = HashMap::<FTAccountId, f64>::new();
+        let mut protocol_accounts_fees = HashMap::<FTAccountId, u16>::new();
 
-        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 0.05);
-        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 0.03);
+        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 500);
+        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 300);
 
         contract
-            .create_new_epoch(Some(protocol_accounts_fees))
+            .create_new_epoch(Some(protocol_accounts_fees), None)
             .unwrap();
 
-        contract.is_epoch_on = true;
-
         let metadata = get_registry_metadata();
 
         assert!(contract
-            .creator_registration(metadata.clone())
+            .creator_registration(metadata.clone(), None)
             .unwrap_err()
             .to_string()
             .contains("Not in Registration period"));
     }
+
+    #[test]
+    fn it_works_creator_registry_with_funding_goal() {
+        let admin: AccountId = accounts(0);
+        let storage = parse_near!("0.1 N");
+
+        let context = get_context(accounts(0), storage);
+        testing_env!(context);
+
+        let mut contract = MetaDaoContract::new(admin.clone());
+
+        let mut protocol_accounts_fees = HashMap::<FTAccountId, u16>::new();
+
+        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 500);
+        protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 300);
+
+        contract
+            .create_new_epoch(Some(protocol_accounts_fees), None)
+            .unwrap();
+
+        contract.set_registration().unwrap();
+
+        let funding_goal = HashMap::<FTAccountId, u128>::from_iter([(
+            "wrap.near".to_string().try_into().unwrap(),
+            1_000_u128,
+        )]);
+
+        contract
+            .creator_registration(get_registry_metadata(), Some(funding_goal.clone()))
+            .unwrap();
+
+        assert_eq!(
+            contract.get_funding_goal(&contract.epoch, &accounts(0)),
+            Some(funding_goal)
+        );
+        assert!(!contract.is_funding_goal_met(&contract.epoch, &accounts(0)));
+        assert!(!contract.is_refundable(&contract.epoch, &accounts(0)));
+    }
+
+    #[test]
+    fn it_fails_creator_registry_if_funding_goal_token_is_not_allowed() {
+        let admin: AccountId = accounts(0);
+        let storage = parse_near!("0.1 N");
+
+        let context = get_context(accounts(0), storage);
+        testing_env!(context);
+
+        let mut contract = MetaDaoContract::new(admin.clone());
+
+        let mut protocol_accounts_fees = HashMap::<FTAccountId, u16>::new();
+
+        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 500);
+
+        contract
+            .create_new_epoch(Some(protocol_accounts_fees), None)
+            .unwrap();
+
+        contract.set_registration().unwrap();
+
+        let funding_goal = HashMap::<FTAccountId, u128>::from_iter([(
+            "usn".to_string().try_into().unwrap(),
+            1_000_u128,
+        )]);
+
+        assert!(contract
+            .creator_registration(get_registry_metadata(), Some(funding_goal))
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid Fungible token id"));
+
+        let funding_goal = HashMap::<FTAccountId, u128>::from_iter([(
+            "wrap.near".to_string().try_into().unwrap(),
+            0_u128,
+        )]);
+
+        assert!(contract
+            .creator_registration(get_registry_metadata(), Some(funding_goal))
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid funding goal"));
+    }
+
+    #[test]
+    fn it_fails_creator_registry_if_already_registered() {
+        let admin: AccountId = accounts(0);
+        let storage = parse_near!("0.1 N");
+
+        let context = get_context(accounts(0), storage);
+        testing_env!(context);
+
+        let mut contract = MetaDaoContract::new(admin.clone());
+
+        let protocol_accounts_fees = HashMap::<FTAccountId, u16>::from_iter([(
+            "wrap.near".to_string().try_into().unwrap(),
+            500,
+        )]);
+
+        contract
+            .create_new_epoch(Some(protocol_accounts_fees), None)
+            .unwrap();
+        contract.set_registration().unwrap();
+
+        contract
+            .creator_registration(get_registry_metadata(), None)
+            .unwrap();
+
+        assert!(matches!(
+            contract.creator_registration(get_registry_metadata(), None),
+            Err(MetaDaoError::CreatorAlreadyRegistered)
+        ));
+    }
+
+    #[test]
+    fn it_works_update_creator_metadata() {
+        let admin: AccountId = accounts(0);
+        let storage = parse_near!("0.1 N");
+
+        let context = get_context(accounts(0), storage);
+        testing_env!(context);
+
+        let mut contract = MetaDaoContract::new(admin.clone());
+
+        let protocol_accounts_fees = HashMap::<FTAccountId, u16>::from_iter([(
+            "wrap.near".to_string().try_into().unwrap(),
+            500,
+        )]);
+
+        contract
+            .create_new_epoch(Some(protocol_accounts_fees), None)
+            .unwrap();
+        contract.set_registration().unwrap();
+
+        assert!(matches!(
+            contract.update_creator_metadata(get_registry_metadata()),
+            Err(MetaDaoError::CreatorIsNotRegistered)
+        ));
+
+        contract
+            .creator_registration(get_registry_metadata(), None)
+            .unwrap();
+
+        let mut metadata = get_registry_metadata();
+        metadata.tiers[2].title = "a much longer rare title".to_string();
+
+        contract.update_creator_metadata(metadata.clone()).unwrap();
+
+        let creators_metadata = contract.creators_metadata.get(&contract.epoch).unwrap();
+        assert_eq!(creators_metadata.get(&accounts(0)).unwrap(), metadata);
+
+        contract.set_funding().unwrap();
+
+        assert!(matches!(
+            contract.update_creator_metadata(get_registry_metadata()),
+            Err(MetaDaoError::NotInRegistrationPeriod)
+        ));
+    }
+
+    #[test]
+    fn it_works_withdraw_registration() {
+        let admin: AccountId = accounts(0);
+        let storage = parse_near!("0.1 N");
+
+        let context = get_context(accounts(0), storage);
+        testing_env!(context);
+
+        let mut contract = MetaDaoContract::new(admin.clone());
+
+        let mut protocol_accounts_fees = HashMap::<FTAccountId, u16>::new();
+        protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 500);
+
+        contract
+            .create_new_epoch(Some(protocol_accounts_fees), None)
+            .unwrap();
+        contract.set_registration().unwrap();
+
+        let funding_goal = HashMap::<FTAccountId, u128>::from_iter([(
+            "wrap.near".to_string().try_into().unwrap(),
+            1_000_u128,
+        )]);
+
+        let initial_storage_usage = env::storage_usage();
+
+        contract
+            .creator_registration(get_registry_metadata(), Some(funding_goal))
+            .unwrap();
+
+        let registration_storage_usage = env::storage_usage() - initial_storage_usage;
+
+        testing_env!(get_context(accounts(0), 0));
+
+        let storage_usage = env::storage_usage();
+
+        contract.withdraw_registration().unwrap();
+
+        let released_storage_usage = storage_usage - env::storage_usage();
+        let released_storage_cost = released_storage_usage as u128 * env::STORAGE_PRICE_PER_BYTE;
+
+        assert_eq!(released_storage_usage, registration_storage_usage);
+        assert!(!contract
+            .creators_per_epoch_set
+            .get(&contract.epoch)
+            .unwrap()
+            .contains(&accounts(0)));
+        assert!(contract
+            .creators_metadata
+            .get(&contract.epoch)
+            .unwrap()
+            .get(&accounts(0))
+            .is_none());
+        assert_eq!(
+            contract.get_funding_goal(&contract.epoch, &accounts(0)),
+            None
+        );
+
+        let refunds: Vec<_> = get_created_receipts()
+            .into_iter()
+            .filter(|receipt| receipt.receiver_id == accounts(0))
+            .flat_map(|receipt| receipt.actions)
+            .collect();
+
+        // the fungible token storage reserve is sent back along with the released storage
+        assert!(contract
+            .creator_ft_storage_reserves
+            .get(&accounts(0))
+            .is_none());
+        assert_eq!(
+            refunds,
+            vec![
+                VmAction::Transfer {
+                    deposit: FT_STORAGE_DEPOSIT
+                },
+                VmAction::Transfer {
+                    deposit: released_storage_cost
+                }
+            ]
+        );
+
+        assert!(matches!(
+            contract.withdraw_registration(),
+            Err(MetaDaoError::CreatorIsNotRegistered)
+        ));
+
+        testing_env!(get_context(accounts(0), storage));
+
+        contract
+            .creator_registration(get_registry_metadata(), None)
+            .unwrap();
+    }
+
+    #[test]
+    fn it_fails_creator_registry_if_metadata_is_invalid() {
+        let admin: AccountId = accounts(0);
+        let storage = parse_near!("0.1 N");
+
+        let context = get_context(accounts(0), storage);
+        testing_env!(context);
+
+        let mut contract = MetaDaoContract::new(admin.clone());
+
+        let protocol_accounts_fees = HashMap::<FTAccountId, u16>::from_iter([(
+            "wrap.near".to_string().try_into().unwrap(),
+            500,
+        )]);
+
+        contract
+            .create_new_epoch(Some(protocol_accounts_fees), None)
+            .unwrap();
+
+        contract.set_registration().unwrap();
+
+        let mut missing_tier = get_registry_metadata();
+        missing_tier.tiers.pop();
+
+        let mut misordered_tiers = get_registry_metadata();
+        misordered_tiers.tiers.swap(0, 2);
+
+        let mut empty_prices = get_registry_metadata();
+        empty_prices.tiers[0].prices = HashMap::new();
+
+        let mut disallowed_token = get_registry_metadata();
+        disallowed_token.tiers[1].prices =
+            HashMap::from_iter([("usn".to_string().try_into().unwrap(), 250_u128)]);
+
+        let mut empty_title = get_registry_metadata();
+        empty_title.tiers[0].title = " ".to_string();
+
+        let mut zero_copies = get_registry_metadata();
+        zero_copies.tiers[2].copies = 0;
+
+        let mut invalid_media = get_registry_metadata();
+        invalid_media.tiers[1].media = "media uncommon".to_string();
+
+        let mut invalid_reference = get_registry_metadata();
+        invalid_reference.tiers[0].reference = Some("ftp://reference".to_string());
+
+        let mut long_title = get_registry_metadata();
+        long_title.tiers[1].title = "a".repeat(MAX_NFT_TITLE_LENGTH + 1);
+
+        let cases = vec![
+            (missing_tier, MetaDaoError::InvalidCreatorMetadataTiers),
+            (misordered_tiers, MetaDaoError::InvalidCreatorMetadataTiers),
+            (empty_prices, MetaDaoError::InvalidNFTPrice),
+            (disallowed_token, MetaDaoError::InvalidFTTokenId),
+            (empty_title, MetaDaoError::EmptyNFTTitle),
+            (zero_copies, MetaDaoError::InvalidNFTCopies),
+            (invalid_media, MetaDaoError::InvalidNFTUrl),
+            (invalid_reference, MetaDaoError::InvalidNFTUrl),
+            (long_title, MetaDaoError::NFTMetadataFieldTooLong),
+        ];
+
+        for (metadata, error) in cases {
+            assert_eq!(
+                contract
+                    .creator_registration(metadata, None)
+                    .unwrap_err()
+                    .to_string(),
+                error.to_string()
+            );
+        }
+
+        let mut reference = get_registry_metadata();
+        reference.tiers[2].reference = Some("https://example.com/rare.json".to_string());
+
+        contract.creator_registration(reference, None).unwrap();
+
+        let mut empty_title = get_registry_metadata();
+        empty_title.tiers[2].title = String::new();
+
+        assert!(matches!(
+            contract.update_creator_metadata(empty_title),
+            Err(MetaDaoError::EmptyNFTTitle)
+        ));
+    }
+
+    #[test]
+    fn it_deserializes_creator_metadata_from_tiers_and_legacy_json() {
+        let metadata = get_registry_metadata();
+
+        let tiers_json = near_sdk::serde_json::to_string(&metadata).unwrap();
+        assert!(tiers_json.starts_with(r#"{"tiers":[{"nft_rank":"Common""#));
+        assert_eq!(
+            near_sdk::serde_json::from_str::<CreatorMetadata>(&tiers_json).unwrap(),
+            metadata
+        );
+
+        let legacy_json = near_sdk::serde_json::to_string(&get_legacy_registry_metadata()).unwrap();
+        assert_eq!(
+            near_sdk::serde_json::from_str::<CreatorMetadata>(&legacy_json).unwrap(),
+            metadata
+        );
+
+        let mut desynced_metadata = get_legacy_registry_metadata();
+        desynced_metadata.medias.swap(0, 1);
+        let desynced_json = near_sdk::serde_json::to_string(&desynced_metadata).unwrap();
+
+        assert!(near_sdk::serde_json::from_str::<CreatorMetadata>(&desynced_json).is_err());
+    }
 }
@@ -0,0 +1,231 @@
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U64;
+use near_sdk::near_bindgen;
+use near_sdk::serde::{Deserialize, Serialize};
+
+use crate::{error::MetaDaoError, phase::EpochPhase, *};
+
+/// [`EpochSchedule`] sets the registration, funding and minting windows of an epoch,
+/// as block timestamps (in nanoseconds). Each window starts at its `start` timestamp,
+/// included, and finishes at its `end` timestamp, excluded
+#[derive(BorshDeserialize, BorshSerialize, Deserialize, Serialize, Debug, Clone, PartialEq)]
+#[serde(crate = "near_sdk::serde")]
+pub struct EpochSchedule {
+    pub registration_start: U64,
+    pub registration_end: U64,
+    pub funding_start: U64,
+    pub funding_end: U64,
+    pub minting_start: U64,
+    pub minting_end: U64,
+}
+
+impl EpochSchedule {
+    /// Checks that every window is non empty, and that windows follow
+    /// the order registration, funding and minting, without overlapping
+    pub fn validate(&self) -> Result<(), MetaDaoError> {
+        let timestamps = [
+            self.registration_start.0,
+            self.registration_end.0,
+            self.funding_start.0,
+            self.funding_end.0,
+            self.minting_start.0,
+            self.minting_end.0,
+        ];
+
+        let is_ordered = timestamps.windows(2).all(|w| w[0] <= w[1])
+            && self.registration_start.0 < self.registration_end.0
+            && self.funding_start.0 < self.funding_end.0
+            && self.minting_start.0 < self.minting_end.0;
+
+        if !is_ordered {
+            return Err(MetaDaoError::InvalidEpochSchedule);
+        }
+
+        Ok(())
+    }
+
+    pub fn is_in_registration(&self, timestamp: u64) -> bool {
+        self.registration_start.0 <= timestamp && timestamp < self.registration_end.0
+    }
+
+    pub fn is_in_funding(&self, timestamp: u64) -> bool {
+        self.funding_start.0 <= timestamp && timestamp < self.funding_end.0
+    }
+
+    pub fn is_funding_over(&self, timestamp: u64) -> bool {
+        self.funding_end.0 <= timestamp
+    }
+
+    /// Returns the latest phase whose window has started at `timestamp`. Once the
+    /// minting window ends, the epoch is settled
+    pub fn phase_at(&self, timestamp: u64) -> EpochPhase {
+        if timestamp < self.registration_start.0 {
+            EpochPhase::Idle
+        } else if timestamp < self.funding_start.0 {
+            EpochPhase::Registration
+        } else if timestamp < self.minting_start.0 {
+            EpochPhase::Funding
+        } else if timestamp < self.minting_end.0 {
+            EpochPhase::Minting
+        } else {
+            EpochPhase::Settled
+        }
+    }
+}
+
+impl MetaDaoContract {
+    /// Returns the schedule of the current epoch, if it is on and was created with one
+    pub fn current_schedule(&self) -> Option<EpochSchedule> {
+        if !self.is_epoch_on() {
+            return None;
+        }
+
+        self.epoch_schedules.get(&self.epoch)
+    }
+
+    /// Manual phase toggles are only allowed for unscheduled epochs
+    pub fn assert_unscheduled_epoch(&self) -> Result<(), MetaDaoError> {
+        if self.current_schedule().is_some() {
+            return Err(MetaDaoError::EpochIsScheduled);
+        }
+
+        Ok(())
+    }
+}
+
+#[near_bindgen]
+impl MetaDaoContract {
+    pub fn get_epoch_schedule(&self, epoch: Epoch) -> Option<EpochSchedule> {
+        self.epoch_schedules.get(&epoch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use near_sdk::test_utils::{accounts, VMContextBuilder};
+    use near_sdk::{testing_env, AccountId, Gas, VMContext};
+
+    fn get_context_with_timestamp(timestamp: u64) -> VMContext {
+        let contract_account_id: AccountId = "conliq.testnet".to_string().try_into().unwrap();
+
+        VMContextBuilder::new()
+            .current_account_id(contract_account_id)
+            .signer_account_id(accounts(1))
+            .predecessor_account_id(accounts(1))
+            .prepaid_gas(Gas(300 * 10u64.pow(16)))
+            .attached_deposit(parse_near!("0.1 N"))
+            .block_timestamp(timestamp)
+            .build()
+    }
+
+    fn get_schedule() -> EpochSchedule {
+        EpochSchedule {
+            registration_start: U64(100),
+            registration_end: U64(200),
+            funding_start: U64(200),
+            funding_end: U64(300),
+            minting_start: U64(300),
+            minting_end: U64(400),
+        }
+    }
+
+    #[test]
+    fn test_epoch_schedule_validate_works() {
+        assert!(get_schedule().validate().is_ok());
+
+        let mut schedule = get_schedule();
+        schedule.funding_start = U64(150);
+
+        assert!(schedule
+            .validate()
+            .unwrap_err()
+            .to_string()
+            .contains("Invalid epoch schedule"));
+
+        let mut schedule = get_schedule();
+        schedule.minting_end = U64(300);
+
+        assert!(schedule.validate().is_err());
+    }
+
+    #[test]
+    fn test_scheduled_epoch_phases_follow_block_timestamp() {
+        testing_env!(get_context_with_timestamp(50));
+
+        let mut contract = MetaDaoContract::new(accounts(1));
+
+        contract
+            .create_new_epoch(None, Some(get_schedule()))
+            .unwrap();
+
+        assert_eq!(contract.get_epoch_schedule(Epoch(1)), Some(get_schedule()));
+        assert!(!contract.is_in_registration());
+
+        testing_env!(get_context_with_timestamp(100));
+        assert!(contract.is_in_registration());
+        assert!(!contract.is_in_funding());
+
+        testing_env!(get_context_with_timestamp(250));
+        assert!(!contract.is_in_registration());
+        assert!(contract.is_in_funding());
+        assert!(!contract.is_funding_over(&Epoch(1)));
+
+        testing_env!(get_context_with_timestamp(300));
+        assert!(!contract.is_in_funding());
+        assert!(contract.is_in_minting());
+        assert!(contract.is_funding_over(&Epoch(1)));
+        assert_eq!(
+            contract.get_epoch_phase(Epoch(1)),
+            Some(EpochPhase::Minting)
+        );
+
+        assert!(matches!(
+            contract.user_funding_creator(
+                accounts(2),
+                accounts(1),
+                UserNFTRank::Common,
+                100_u128,
+                "wrap.near".to_string().try_into().unwrap(),
+            ),
+            Err(MetaDaoError::NotInFundingPeriod)
+        ));
+
+        testing_env!(get_context_with_timestamp(400));
+        assert_eq!(
+            contract.get_epoch_phase(Epoch(1)),
+            Some(EpochPhase::Settled)
+        );
+        assert!(!contract.is_epoch_on());
+
+        assert!(matches!(
+            contract.user_funding_creator(
+                accounts(2),
+                accounts(1),
+                UserNFTRank::Common,
+                100_u128,
+                "wrap.near".to_string().try_into().unwrap(),
+            ),
+            Err(MetaDaoError::EpochIsOff)
+        ));
+
+        contract.create_new_epoch(None, None).unwrap();
+    }
+
+    #[test]
+    fn test_manual_toggles_fail_if_epoch_is_scheduled() {
+        testing_env!(get_context_with_timestamp(50));
+
+        let mut contract = MetaDaoContract::new(accounts(1));
+
+        contract
+            .create_new_epoch(None, Some(get_schedule()))
+            .unwrap();
+
+        assert!(contract
+            .set_registration()
+            .unwrap_err()
+            .to_string()
+            .contains("Epoch phases are scheduled"));
+    }
+}
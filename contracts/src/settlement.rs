@@ -0,0 +1,773 @@
+use near_contract_standards::non_fungible_token::events::NftMint;
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::serde::{Deserialize, Serialize};
+use near_sdk::{env, near_bindgen, StorageUsage};
+
+use crate::{
+    consts::GAS_FOR_SETTLEMENT_ENTRY,
+    error::MetaDaoError,
+    events::{FeeCollectionLog, TransferLog},
+    fees::split_protocol_fee,
+    provenance::NFTProvenance,
+    registry::CreatorMetadata,
+    *,
+};
+
+/// [`PayoutStatus`] tracks the release of a funding entry to the creator
+#[derive(
+    BorshDeserialize, BorshSerialize, Deserialize, Serialize, Debug, Clone, Copy, Eq, PartialEq,
+)]
+#[serde(crate = "near_sdk::serde")]
+pub enum PayoutStatus {
+    /// The funds are held by the contract
+    Pending,
+    /// The net proceeds were credited to the creator balance
+    Credited,
+}
+
+impl MetaDaoContract {
+    /// Checks if `epoch` is in the minting phase, or settled, so that its creators can be settled
+    pub fn assert_in_settlement_phase(&self, epoch: &Epoch) -> Result<(), MetaDaoError> {
+        match self.get_epoch_phase(*epoch) {
+            Some(EpochPhase::Minting | EpochPhase::Settled) => Ok(()),
+            _ => Err(MetaDaoError::NotInMintingPeriod),
+        }
+    }
+
+    /// Checks if the caller is allowed to settle `creator_account_id`, i.e. it is the creator,
+    /// the admin or the settlement operator
+    pub fn assert_can_settle_creator(
+        &self,
+        creator_account_id: &CreatorAccountId,
+    ) -> Result<(), MetaDaoError> {
+        let caller = env::predecessor_account_id();
+
+        if caller != *creator_account_id
+            && caller != self.admin
+            && self.settlement_operator.as_ref() != Some(&caller)
+        {
+            return Err(MetaDaoError::InvalidSettlementCall);
+        }
+
+        Ok(())
+    }
+
+    /// Checks if the funds of `creator_account_id` can be released, and its NFTs minted, in `epoch`
+    pub fn assert_creator_can_be_settled(
+        &self,
+        epoch: &Epoch,
+        creator_account_id: &CreatorAccountId,
+    ) -> Result<(), MetaDaoError> {
+        // creators with a funding goal only release funds once the funding period is over
+        // and the goal is met, otherwise their backers can claim refunds
+        if self.get_funding_goal(epoch, creator_account_id).is_some() {
+            if !self.is_funding_over(epoch) {
+                return Err(MetaDaoError::FundingIsNotOver);
+            }
+
+            if !self.is_funding_goal_met(epoch, creator_account_id) {
+                return Err(MetaDaoError::FundingGoalNotMet);
+            }
+        }
+
+        if self.is_refundable(epoch, creator_account_id) {
+            return Err(MetaDaoError::FundsAreRefundable);
+        }
+
+        Ok(())
+    }
+
+    /// Mints the NFT of `entry`, the funding entry at `funding_index` of `creator_account_id`
+    /// in `epoch`, for its backer, and records its provenance. The storage of the NFT is paid out
+    /// of the attached deposit, with the excess refunded to `refund_id`, if any
+    fn mint_entry_nft(
+        &mut self,
+        epoch: Epoch,
+        creator_account_id: &CreatorAccountId,
+        creator_metadata: &CreatorMetadata,
+        entry: &mut ObtainedTokenAmounts,
+        funding_index: u64,
+        refund_id: Option<AccountId>,
+    ) -> Result<(), MetaDaoError> {
+        let token_id = self.get_token_id(
+            &entry.user_id,
+            &entry.nft_rank,
+            entry.edition,
+            creator_metadata,
+        );
+        let token_metadata =
+            creator_metadata.get_token_metadata(entry.nft_rank.clone(), entry.edition)?;
+
+        let token = self.tokens.internal_mint_with_refund(
+            token_id.clone(),
+            entry.user_id.clone(),
+            Some(token_metadata),
+            refund_id,
+        );
+        NftMint {
+            owner_id: &token.owner_id,
+            token_ids: &[&token.token_id],
+            memo: None,
+        }
+        .emit();
+
+        // update nft id
+        self.nft_id += 1;
+
+        self.record_nft_provenance(
+            &token_id,
+            NFTProvenance {
+                epoch,
+                creator_id: creator_account_id.clone(),
+                nft_rank: entry.nft_rank.clone(),
+                edition: entry.edition,
+                user_id: entry.user_id.clone(),
+                ft_token_id: entry.ft_token_id.clone(),
+                amount: U128(entry.amount),
+                funding_index,
+            },
+        )?;
+
+        entry.minted = true;
+
+        Ok(())
+    }
+
+    /// Settles the funding entries of `creator_account_id` in `epoch`, in range
+    /// `[from_index, from_index + limit)`, crediting the net proceeds of pending entries and, if `mint` is set, minting the NFTs
+    /// not claimed yet, whose storage is paid by the caller out of the attached deposit
+    fn internal_settle_creator(
+        &mut self,
+        epoch: Epoch,
+        creator_account_id: CreatorAccountId,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+        mint: bool,
+    ) -> Result<Option<u64>, MetaDaoError> {
+        self.assert_can_settle_creator(&creator_account_id)?;
+        self.assert_in_settlement_phase(&epoch)?;
+
+        let creators_metadata = self
+            .creators_metadata
+            .get(&epoch)
+            .ok_or(MetaDaoError::InvalidCurrentEpoch)?;
+
+        let creator_metadata = creators_metadata
+            .get(&creator_account_id)
+            .ok_or(MetaDaoError::CreatorIsNotRegistered)?;
+
+        self.assert_creator_can_be_settled(&epoch, &creator_account_id)?;
+
+        let mut creator_fundings = self
+            .creator_funding
+            .get(&epoch)
+            .ok_or(MetaDaoError::InvalidCurrentEpoch)?;
+
+        let mut creator_funding = creator_fundings
+            .get(&creator_account_id)
+            .ok_or(MetaDaoError::CreatorIsNotRegistered)?;
+
+        let entries_count = creator_funding.len() as u64;
+        let from_index = from_index.unwrap_or(0);
+        let end_index = from_index
+            .saturating_add(limit.unwrap_or(u64::MAX))
+            .min(entries_count);
+
+        let mut payouts = vec![];
+        let mut index = from_index;
+        // the bytes used by the minted NFTs, paid for once out of the attached deposit
+        let mut minted_storage_usage: StorageUsage = 0;
+
+        while index < end_index {
+            if env::prepaid_gas().0.saturating_sub(env::used_gas().0) < GAS_FOR_SETTLEMENT_ENTRY.0 {
+                break;
+            }
+
+            let entry = &mut creator_funding[index as usize];
+
+            let needs_mint = mint && !entry.minted;
+            let needs_payout = entry.payout_status == PayoutStatus::Pending;
+
+            if entry.already_refunded || !(needs_mint || needs_payout) {
+                index += 1;
+                continue;
+            }
+
+            if needs_mint {
+                let storage_usage = env::storage_usage();
+
+                self.mint_entry_nft(
+                    epoch,
+                    &creator_account_id,
+                    &creator_metadata,
+                    entry,
+                    index,
+                    None,
+                )?;
+
+                minted_storage_usage += env::storage_usage() - storage_usage;
+            }
+
+            if needs_payout {
+                let protocol_fee = self
+                    .get_protocol_fee(&epoch, &entry.ft_token_id)
+                    .ok_or(MetaDaoError::InvalidFTTokenId)?;
+
+                let (creator_amount_to_receive, protocol_fee_amount) =
+                    split_protocol_fee(entry.amount, protocol_fee)?;
+
+                self.accrue_protocol_fee(&entry.ft_token_id, protocol_fee_amount)?;
+
+                MetaStageEvent::FeeCollection(vec![FeeCollectionLog {
+                    epoch: epoch.count(),
+                    creator_id: creator_account_id.clone(),
+                    ft_token_id: entry.ft_token_id.clone(),
+                    amount: U128(protocol_fee_amount),
+                }])
+                .emit();
+
+                self.credit_creator(
+                    &creator_account_id,
+                    &entry.ft_token_id,
+                    creator_amount_to_receive,
+                )?;
+
+                entry.payout_status = PayoutStatus::Credited;
+
+                payouts.push(TransferLog {
+                    epoch: epoch.count(),
+                    creator_id: creator_account_id.clone(),
+                    user_id: entry.user_id.clone(),
+                    ft_token_id: entry.ft_token_id.clone(),
+                    amount: U128(entry.amount),
+                });
+            }
+
+            index += 1;
+        }
+
+        creator_fundings.insert(&creator_account_id, &creator_funding);
+        self.creator_funding.insert(&epoch, &creator_fundings);
+
+        if !payouts.is_empty() {
+            MetaStageEvent::Payout(payouts).emit();
+        }
+
+        if mint {
+            self.settle_attached_storage(
+                &env::predecessor_account_id(),
+                env::storage_usage() - minted_storage_usage,
+                0,
+            )?;
+        }
+
+        Ok(if index < entries_count {
+            Some(index)
+        } else {
+            None
+        })
+    }
+}
+
+#[near_bindgen]
+impl MetaDaoContract {
+    /// Settles the funding entries of `creator_account_id` in `epoch`, in range
+    /// `[from_index, from_index + limit)`: entries get their NFT minted, unless their backer already
+    /// claimed it, and the net proceeds of pending entries are credited to the creator balance, to be
+    /// withdrawn via `withdraw`. Entries which are settled or refunded are skipped, so it is safe to
+    /// call it again until the creator is fully settled, also once later epochs started. Only the
+    /// creator, the admin or the settlement operator can call it. It stops early if the remaining gas
+    /// does not cover another entry, and returns the index to resume from, if any entries are left
+    #[payable]
+    #[handle_result]
+    pub fn settle_creator(
+        &mut self,
+        epoch: Epoch,
+        creator_account_id: CreatorAccountId,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Option<u64>, MetaDaoError> {
+        self.internal_settle_creator(epoch, creator_account_id, from_index, limit, true)
+    }
+
+    /// Credits the net proceeds of the pending funding entries of `creator_account_id` in `epoch`,
+    /// in range `[from_index, from_index + limit)`, without minting their NFTs, which backers mint via
+    /// `claim_nft`. Only the creator, the admin or the settlement operator can call it. Returns the
+    /// index to resume from, if any entries are left
+    #[handle_result]
+    pub fn release_creator_payout(
+        &mut self,
+        epoch: Epoch,
+        creator_account_id: CreatorAccountId,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> Result<Option<u64>, MetaDaoError> {
+        self.internal_settle_creator(epoch, creator_account_id, from_index, limit, false)
+    }
+
+    /// Mints the NFTs the caller pledged for to `creator_account_id` in `epoch`, once its funding is
+    /// over. The attached deposit has to cover the storage of the minted NFTs, and the excess is
+    /// refunded. Returns the number of minted NFTs
+    #[payable]
+    #[handle_result]
+    pub fn claim_nft(
+        &mut self,
+        epoch: Epoch,
+        creator_account_id: CreatorAccountId,
+    ) -> Result<u64, MetaDaoError> {
+        let user_id = env::predecessor_account_id();
+
+        if !self.is_funding_over(&epoch) {
+            return Err(MetaDaoError::FundingIsNotOver);
+        }
+
+        self.assert_creator_can_be_settled(&epoch, &creator_account_id)?;
+
+        let creator_metadata = self
+            .creators_metadata
+            .get(&epoch)
+            .ok_or(MetaDaoError::InvalidCurrentEpoch)?
+            .get(&creator_account_id)
+            .ok_or(MetaDaoError::CreatorIsNotRegistered)?;
+
+        let mut creator_fundings = self
+            .creator_funding
+            .get(&epoch)
+            .ok_or(MetaDaoError::InvalidCurrentEpoch)?;
+
+        let mut creator_funding = creator_fundings
+            .get(&creator_account_id)
+            .ok_or(MetaDaoError::CreatorIsNotRegistered)?;
+
+        let initial_storage_usage = env::storage_usage();
+        let mut minted = 0;
+
+        for (index, entry) in creator_funding.iter_mut().enumerate() {
+            if entry.user_id != user_id || entry.already_refunded || entry.minted {
+                continue;
+            }
+
+            self.mint_entry_nft(
+                epoch,
+                &creator_account_id,
+                &creator_metadata,
+                entry,
+                index as u64,
+                None,
+            )?;
+
+            minted += 1;
+        }
+
+        if minted == 0 {
+            return Err(MetaDaoError::NoNFTToClaim);
+        }
+
+        creator_fundings.insert(&creator_account_id, &creator_funding);
+        self.creator_funding.insert(&epoch, &creator_fundings);
+
+        self.settle_attached_storage(&user_id, initial_storage_usage as StorageUsage, 0)?;
+
+        Ok(minted)
+    }
+
+    /// Sets the account allowed, besides the admin and the creators, to settle creators, or
+    /// removes it if `None`
+    #[handle_result]
+    pub fn set_settlement_operator(
+        &mut self,
+        operator_id: Option<AccountId>,
+    ) -> Result<(), MetaDaoError> {
+        if env::predecessor_account_id() != self.admin {
+            return Err(MetaDaoError::InvalidAdminCall);
+        }
+
+        self.settlement_operator = operator_id;
+
+        Ok(())
+    }
+
+    /// Checks if every funding entry of `creator_account_id` in `epoch`, which was not
+    /// refunded, had its NFT minted and was credited to the creator
+    pub fn is_creator_settled(&self, epoch: Epoch, creator_account_id: CreatorAccountId) -> bool {
+        self.creator_funding
+            .get(&epoch)
+            .and_then(|creator_fundings| creator_fundings.get(&creator_account_id))
+            .map(|creator_funding| {
+                creator_funding.iter().all(|ot| {
+                    ot.already_refunded || (ot.minted && ot.payout_status == PayoutStatus::Credited)
+                })
+            })
+            .unwrap_or(false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fund_creators::CreatorBalanceView;
+    use crate::tests::{fund_creator, get_context, get_contract_in_funding};
+    use near_contract_standards::non_fungible_token::enumeration::NonFungibleTokenEnumeration;
+    use near_sdk::{
+        mock::VmAction,
+        test_utils::{accounts, get_created_receipts},
+        testing_env,
+    };
+
+    fn get_funded_contract() -> MetaDaoContract {
+        let mut contract = get_contract_in_funding(accounts(1));
+
+        fund_creator(
+            &mut contract,
+            &[
+                ("user.near", UserNFTRank::Common, "wrap.near", 100),
+                ("user.near", UserNFTRank::Common, "usn", 1_000),
+                ("other_user.near", UserNFTRank::Common, "wrap.near", 100),
+            ],
+        );
+
+        contract.set_minting().unwrap();
+
+        contract
+    }
+
+    fn payout_statuses(contract: &MetaDaoContract) -> Vec<PayoutStatus> {
+        contract
+            .creator_funding
+            .get(&Epoch(1))
+            .unwrap()
+            .get(&accounts(1))
+            .unwrap()
+            .iter()
+            .map(|ot| ot.payout_status)
+            .collect()
+    }
+
+    #[test]
+    fn test_settle_creator_is_paginated_and_idempotent() {
+        let mut contract = get_funded_contract();
+
+        assert_eq!(
+            contract
+                .settle_creator(Epoch(1), accounts(1), Some(0), Some(2))
+                .unwrap(),
+            Some(2)
+        );
+        assert_eq!(
+            payout_statuses(&contract),
+            vec![
+                PayoutStatus::Credited,
+                PayoutStatus::Credited,
+                PayoutStatus::Pending
+            ]
+        );
+        assert_eq!(contract.nft_id, 2);
+        assert_eq!(
+            contract.protocol_fees.get(&"usn".parse().unwrap()),
+            Some(30)
+        );
+
+        // credited entries are not minted, nor credited, twice
+        assert_eq!(
+            contract
+                .settle_creator(Epoch(1), accounts(1), None, None)
+                .unwrap(),
+            None
+        );
+        assert_eq!(payout_statuses(&contract), vec![PayoutStatus::Credited; 3]);
+        assert_eq!(contract.nft_id, 3);
+        assert_eq!(
+            contract.protocol_fees.get(&"wrap.near".parse().unwrap()),
+            Some(10)
+        );
+
+        assert_eq!(
+            contract
+                .settle_creator(Epoch(1), accounts(1), None, None)
+                .unwrap(),
+            None
+        );
+        assert_eq!(contract.nft_id, 3);
+        assert!(contract.is_creator_settled(Epoch(1), accounts(1)));
+    }
+
+    #[test]
+    fn test_settle_creator_settles_past_epochs_with_their_fees() {
+        let mut contract = get_funded_contract();
+
+        contract.end_epoch().unwrap();
+        contract
+            .create_new_epoch(
+                Some(HashMap::from([("wrap.near".parse().unwrap(), 0)])),
+                None,
+            )
+            .unwrap();
+        assert_eq!(contract.epoch, Epoch(2));
+
+        // the creator is left unsettled in the first epoch, which is settled in the second one
+        assert!(!contract.is_creator_settled(Epoch(1), accounts(1)));
+        assert!(matches!(
+            contract.settle_creator(Epoch(2), accounts(1), None, None),
+            Err(MetaDaoError::NotInMintingPeriod)
+        ));
+
+        assert_eq!(
+            contract
+                .settle_creator(Epoch(1), accounts(1), None, None)
+                .unwrap(),
+            None
+        );
+        assert!(contract.is_creator_settled(Epoch(1), accounts(1)));
+        assert_eq!(contract.nft_id, 3);
+
+        // the entries are charged the fees of the first epoch
+        assert_eq!(
+            contract.protocol_fees.get(&"wrap.near".parse().unwrap()),
+            Some(10)
+        );
+        assert_eq!(
+            contract.protocol_fees.get(&"usn".parse().unwrap()),
+            Some(30)
+        );
+    }
+
+    #[test]
+    fn test_settle_creator_charges_the_attached_deposit_once() {
+        let mut contract = get_funded_contract();
+
+        contract
+            .release_creator_payout(Epoch(1), accounts(1), None, None)
+            .unwrap();
+
+        let deposit = parse_near!("0.1 N");
+        testing_env!(get_context(accounts(1), deposit));
+
+        let initial_storage_usage = env::storage_usage();
+        contract
+            .settle_creator(Epoch(1), accounts(1), None, None)
+            .unwrap();
+
+        // the storage of the three NFTs is kept out of the deposit, and the rest refunded once
+        let storage_cost =
+            (env::storage_usage() - initial_storage_usage) as u128 * env::STORAGE_PRICE_PER_BYTE;
+        assert_eq!(contract.nft_total_supply().0, 3);
+
+        let refunds: Vec<_> = get_created_receipts()
+            .into_iter()
+            .flat_map(|receipt| {
+                assert_eq!(receipt.receiver_id, accounts(1));
+                receipt.actions
+            })
+            .collect();
+
+        assert_eq!(
+            refunds,
+            vec![VmAction::Transfer {
+                deposit: deposit - storage_cost
+            }]
+        );
+    }
+
+    #[test]
+    fn test_settle_creator_credits_creator_balance() {
+        let mut contract = get_funded_contract();
+
+        contract
+            .settle_creator(Epoch(1), accounts(1), None, None)
+            .unwrap();
+
+        // nothing is transferred to the creator on settlement
+        assert!(get_created_receipts()
+            .iter()
+            .all(|receipt| !matches!(receipt.receiver_id.as_str(), "wrap.near" | "usn")));
+
+        assert_eq!(
+            contract.creator_balances(accounts(1)),
+            HashMap::from([
+                (
+                    "wrap.near".parse().unwrap(),
+                    CreatorBalanceView {
+                        raised: U128(190),
+                        withdrawn: U128(0),
+                        claimable: U128(190),
+                    }
+                ),
+                (
+                    "usn".parse().unwrap(),
+                    CreatorBalanceView {
+                        raised: U128(970),
+                        withdrawn: U128(0),
+                        claimable: U128(970),
+                    }
+                ),
+            ])
+        );
+
+        contract
+            .withdraw("usn".parse().unwrap(), U128(970))
+            .unwrap();
+
+        assert_eq!(
+            contract.creator_balances(accounts(1))[&"usn".parse().unwrap()].claimable,
+            U128(0)
+        );
+    }
+
+    #[test]
+    fn test_settle_creator_mints_editions_in_pledge_order() {
+        let mut contract = get_funded_contract();
+
+        contract
+            .settle_creator(Epoch(1), accounts(1), None, None)
+            .unwrap();
+
+        let editions: Vec<u64> = contract
+            .creator_funding
+            .get(&Epoch(1))
+            .unwrap()
+            .get(&accounts(1))
+            .unwrap()
+            .iter()
+            .map(|ot| ot.edition)
+            .collect();
+        assert_eq!(editions, vec![1, 2, 3]);
+
+        let tokens = contract.nft_tokens_for_owner("other_user.near".parse().unwrap(), None, None);
+        assert_eq!(tokens.len(), 1);
+        assert_eq!(
+            tokens[0].token_id,
+            "MetaDao|2|common|common|3|other_user.near|"
+        );
+
+        let token_metadata = tokens[0].metadata.clone().unwrap();
+        assert_eq!(token_metadata.title, Some("common (3 of 100)".to_string()));
+        assert_eq!(
+            near_sdk::serde_json::from_str::<near_sdk::serde_json::Value>(
+                &token_metadata.extra.unwrap()
+            )
+            .unwrap(),
+            near_sdk::serde_json::json!({
+                "edition": 3,
+                "copies": 100,
+                "extra": "extra_common",
+            })
+        );
+    }
+
+    #[test]
+    fn test_claim_nft_mints_caller_nfts_and_charges_storage() {
+        let mut contract = get_funded_contract();
+
+        let user: AccountId = "user.near".parse().unwrap();
+        testing_env!(get_context(user.clone(), parse_near!("1 N")));
+
+        assert_eq!(contract.claim_nft(Epoch(1), accounts(1)).unwrap(), 2);
+
+        assert_eq!(
+            contract
+                .nft_tokens_for_owner(user.clone(), None, None)
+                .len(),
+            2
+        );
+        assert!(contract
+            .nft_tokens_for_owner("other_user.near".parse().unwrap(), None, None)
+            .is_empty());
+
+        // the excess of the attached deposit is refunded
+        assert!(get_created_receipts()
+            .iter()
+            .any(|receipt| receipt.receiver_id == user));
+
+        // claims do not release the funds to the creator
+        assert_eq!(payout_statuses(&contract), vec![PayoutStatus::Pending; 3]);
+
+        assert!(matches!(
+            contract.claim_nft(Epoch(1), accounts(1)),
+            Err(MetaDaoError::NoNFTToClaim)
+        ));
+
+        testing_env!(get_context(user, 0));
+        assert!(matches!(
+            contract.claim_nft(Epoch(1), accounts(1)),
+            Err(MetaDaoError::NoNFTToClaim)
+        ));
+
+        testing_env!(get_context("other_user.near".parse().unwrap(), 0));
+        assert!(matches!(
+            contract.claim_nft(Epoch(1), accounts(1)),
+            Err(MetaDaoError::UncoveredStorageCosts)
+        ));
+    }
+
+    #[test]
+    fn test_settlement_is_restricted_on_every_path() {
+        let mut contract = get_funded_contract();
+
+        testing_env!(get_context(accounts(2), parse_near!("0.1 N")));
+        assert!(matches!(
+            contract.settle_creator(Epoch(1), accounts(1), None, None),
+            Err(MetaDaoError::InvalidSettlementCall)
+        ));
+        assert!(matches!(
+            contract.release_creator_payout(Epoch(1), accounts(1), None, None),
+            Err(MetaDaoError::InvalidSettlementCall)
+        ));
+        assert!(matches!(
+            contract.mint_nfts_for_users(Epoch(1), accounts(1)),
+            Err(MetaDaoError::InvalidSettlementCall)
+        ));
+        assert_eq!(payout_statuses(&contract), vec![PayoutStatus::Pending; 3]);
+
+        testing_env!(get_context(accounts(1), 0));
+        contract.set_settlement_operator(Some(accounts(2))).unwrap();
+
+        testing_env!(get_context(accounts(2), 0));
+        contract
+            .release_creator_payout(Epoch(1), accounts(1), None, None)
+            .unwrap();
+        assert_eq!(payout_statuses(&contract), vec![PayoutStatus::Credited; 3]);
+    }
+
+    #[test]
+    fn test_release_creator_payout_is_independent_of_claims() {
+        let mut contract = get_funded_contract();
+
+        assert_eq!(
+            contract
+                .release_creator_payout(Epoch(1), accounts(1), None, None)
+                .unwrap(),
+            None
+        );
+        assert_eq!(payout_statuses(&contract), vec![PayoutStatus::Credited; 3]);
+        assert_eq!(
+            contract.creator_balances(accounts(1))[&"wrap.near".parse().unwrap()].claimable,
+            U128(190)
+        );
+        assert_eq!(contract.nft_total_supply(), U128(0));
+
+        testing_env!(get_context(
+            "user.near".parse().unwrap(),
+            parse_near!("1 N")
+        ));
+        assert_eq!(contract.claim_nft(Epoch(1), accounts(1)).unwrap(), 2);
+
+        // settlement mints the unclaimed NFTs, without crediting the creator twice
+        testing_env!(get_context(accounts(1), parse_near!("1 N")));
+        contract
+            .settle_creator(Epoch(1), accounts(1), None, None)
+            .unwrap();
+
+        assert_eq!(contract.nft_total_supply(), U128(3));
+        assert_eq!(
+            contract
+                .nft_tokens_for_owner("other_user.near".parse().unwrap(), None, None)
+                .len(),
+            1
+        );
+        assert_eq!(
+            contract.creator_balances(accounts(1))[&"wrap.near".parse().unwrap()].claimable,
+            U128(190)
+        );
+    }
+}
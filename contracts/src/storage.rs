@@ -0,0 +1,443 @@
+use near_contract_standards::storage_management::{
+    StorageBalance, StorageBalanceBounds, StorageManagement,
+};
+use near_sdk::borsh::{self, BorshDeserialize, BorshSerialize};
+use near_sdk::json_types::U128;
+use near_sdk::{assert_one_yocto, env, near_bindgen, AccountId, Balance, Promise, StorageUsage};
+
+use crate::{error::MetaDaoError, events::EpochArchiveLog, *};
+
+/// [`AccountStorage`] is the NEP-145 storage balance of an account: the NEAR it deposited,
+/// and the bytes charged to it, including its own entry
+#[derive(BorshDeserialize, BorshSerialize, Debug, Clone, PartialEq)]
+pub struct AccountStorage {
+    pub deposit: Balance,
+    pub used: StorageUsage,
+}
+
+impl AccountStorage {
+    pub fn available(&self) -> Balance {
+        self.deposit
+            .saturating_sub(self.used as Balance * env::STORAGE_PRICE_PER_BYTE)
+    }
+
+    fn to_storage_balance(&self) -> StorageBalance {
+        StorageBalance {
+            total: U128(self.deposit),
+            available: U128(self.available()),
+        }
+    }
+}
+
+impl MetaDaoContract {
+    /// Measures the bytes used by the storage entry of an account with the longest id,
+    /// which is the minimum storage balance of an account
+    pub fn measure_account_storage_usage(&mut self) {
+        let initial_storage_usage = env::storage_usage();
+        let tmp_account_id = AccountId::new_unchecked("a".repeat(64));
+
+        self.storage_accounts.insert(
+            &tmp_account_id,
+            &AccountStorage {
+                deposit: 0,
+                used: 0,
+            },
+        );
+        self.account_storage_usage = env::storage_usage() - initial_storage_usage;
+        self.storage_accounts.remove(&tmp_account_id);
+    }
+
+    /// Checks that the available storage balance of `account_id` covers `storage_usage` bytes
+    pub fn assert_storage_is_covered(
+        &self,
+        account_id: &AccountId,
+        storage_usage: StorageUsage,
+    ) -> Result<(), MetaDaoError> {
+        let available = self
+            .storage_accounts
+            .get(account_id)
+            .map(|account_storage| account_storage.available())
+            .unwrap_or(0);
+
+        if available < storage_usage as Balance * env::STORAGE_PRICE_PER_BYTE {
+            return Err(MetaDaoError::InsufficientStorageBalance);
+        }
+
+        Ok(())
+    }
+
+    /// Charges the bytes used since `initial_storage_usage` to the storage balance of
+    /// `account_id`, for the current epoch. They are released once the epoch is archived
+    pub fn charge_storage(
+        &mut self,
+        account_id: &AccountId,
+        initial_storage_usage: StorageUsage,
+    ) -> Result<(), MetaDaoError> {
+        let mut epoch_storage_usage = self
+            .epoch_storage_usage
+            .get(&self.epoch)
+            .ok_or(MetaDaoError::InvalidCurrentEpoch)?;
+
+        // the entry is written first, so that its own bytes are charged as well
+        let previous_usage = epoch_storage_usage.get(account_id).unwrap_or(0);
+        epoch_storage_usage.insert(account_id, &previous_usage);
+
+        let used = env::storage_usage().saturating_sub(initial_storage_usage);
+
+        let mut account_storage = self
+            .storage_accounts
+            .get(account_id)
+            .ok_or(MetaDaoError::InsufficientStorageBalance)?;
+
+        if account_storage.available() < used as Balance * env::STORAGE_PRICE_PER_BYTE {
+            return Err(MetaDaoError::InsufficientStorageBalance);
+        }
+
+        account_storage.used += used;
+        self.storage_accounts.insert(account_id, &account_storage);
+
+        epoch_storage_usage.insert(account_id, &(previous_usage + used));
+        self.epoch_storage_usage
+            .insert(&self.epoch, &epoch_storage_usage);
+
+        Ok(())
+    }
+}
+
+#[near_bindgen]
+impl MetaDaoContract {
+    /// Removes the funding state of a settled, or cancelled, `epoch`, and releases
+    /// the storage charged to its backers. Every funding entry of the epoch has to be
+    /// either minted and credited to its creator, or refunded. NFTs their backers did not
+    /// claim are minted via `settle_creator`, which also settles epochs already over
+    #[handle_result]
+    pub fn archive_epoch(&mut self, epoch: Epoch) -> Result<(), MetaDaoError> {
+        if env::predecessor_account_id() != self.admin {
+            return Err(MetaDaoError::InvalidAdminCall);
+        }
+
+        if !matches!(self.get_epoch_phase(epoch), Some(phase) if phase.is_terminal()) {
+            return Err(MetaDaoError::EpochCannotBeArchived);
+        }
+
+        let mut creator_fundings = self
+            .creator_funding
+            .get(&epoch)
+            .ok_or(MetaDaoError::EpochCannotBeArchived)?;
+
+        if creator_fundings.values().any(|creator_funding| {
+            creator_funding.iter().any(|ot| {
+                !ot.already_refunded && (!ot.minted || ot.payout_status != PayoutStatus::Credited)
+            })
+        }) {
+            return Err(MetaDaoError::EpochCannotBeArchived);
+        }
+
+        // creators registered before the upgrade are only settled once migrated
+        if self.legacy_creator_funding.get(&epoch).is_some()
+            || self.legacy_creators_metadata.get(&epoch).is_some()
+        {
+            return Err(MetaDaoError::EpochCannotBeArchived);
+        }
+
+        // refunded entries are only restored once their transfer failed, which is not known yet
+        if self.pending_refunds.get(&epoch).unwrap_or(0) > 0 {
+            return Err(MetaDaoError::EpochCannotBeArchived);
+        }
+
+        if let Some(mut epoch_storage_usage) = self.epoch_storage_usage.remove(&epoch) {
+            for (account_id, usage) in epoch_storage_usage.iter() {
+                if let Some(mut account_storage) = self.storage_accounts.get(&account_id) {
+                    account_storage.used = account_storage.used.saturating_sub(usage);
+                    self.storage_accounts.insert(&account_id, &account_storage);
+                }
+            }
+            epoch_storage_usage.clear();
+        }
+
+        creator_fundings.clear();
+        self.creator_funding.remove(&epoch);
+
+        if let Some(mut user_funds) = self.user_funds.remove(&epoch) {
+            user_funds.clear();
+        }
+        if let Some(mut creators_metadata) = self.creators_metadata.remove(&epoch) {
+            creators_metadata.clear();
+        }
+        if let Some(mut creators_per_epoch) = self.creators_per_epoch_set.remove(&epoch) {
+            creators_per_epoch.clear();
+        }
+        if let Some(mut creators_funding_goals) = self.creators_funding_goals.remove(&epoch) {
+            creators_funding_goals.clear();
+        }
+        if let Some(mut refundable_creators) = self.refundable_creators.remove(&epoch) {
+            refundable_creators.clear();
+        }
+        if let Some(mut tier_sold_copies) = self.tier_sold_copies.remove(&epoch) {
+            tier_sold_copies.clear();
+        }
+        self.epoch_protocol_fees.remove(&epoch);
+
+        MetaStageEvent::EpochArchive(vec![EpochArchiveLog {
+            epoch: epoch.count(),
+        }])
+        .emit();
+
+        Ok(())
+    }
+}
+
+#[near_bindgen]
+impl StorageManagement for MetaDaoContract {
+    /// Deposits the attached NEAR to the storage balance of `account_id`, or of the caller.
+    /// With `registration_only`, only the minimum balance is kept, and the rest is refunded
+    #[payable]
+    fn storage_deposit(
+        &mut self,
+        account_id: Option<AccountId>,
+        registration_only: Option<bool>,
+    ) -> StorageBalance {
+        let amount = env::attached_deposit();
+        let account_id = account_id.unwrap_or_else(env::predecessor_account_id);
+        let registration_only = registration_only.unwrap_or(false);
+
+        let account_storage = match self.storage_accounts.get(&account_id) {
+            Some(account_storage) if registration_only => {
+                if amount > 0 {
+                    Promise::new(env::predecessor_account_id()).transfer(amount);
+                }
+                account_storage
+            }
+            Some(mut account_storage) => {
+                account_storage.deposit += amount;
+                account_storage
+            }
+            None => {
+                let min_balance = self.storage_balance_bounds().min.0;
+                if amount < min_balance {
+                    env::panic_str("MetaDaoContract::storage_deposit::The attached deposit is less than the minimum storage balance");
+                }
+
+                let deposit = if registration_only {
+                    if amount > min_balance {
+                        Promise::new(env::predecessor_account_id()).transfer(amount - min_balance);
+                    }
+                    min_balance
+                } else {
+                    amount
+                };
+
+                AccountStorage {
+                    deposit,
+                    used: self.account_storage_usage,
+                }
+            }
+        };
+
+        self.storage_accounts.insert(&account_id, &account_storage);
+
+        account_storage.to_storage_balance()
+    }
+
+    #[payable]
+    fn storage_withdraw(&mut self, amount: Option<U128>) -> StorageBalance {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+
+        let mut account_storage = self.storage_accounts.get(&account_id).unwrap_or_else(|| {
+            env::panic_str("MetaDaoContract::storage_withdraw::The account is not registered")
+        });
+
+        let available = account_storage.available();
+        let amount = amount.map(|amount| amount.0).unwrap_or(available);
+
+        if amount > available {
+            env::panic_str("MetaDaoContract::storage_withdraw::The amount is greater than the available storage balance");
+        }
+
+        if amount > 0 {
+            account_storage.deposit -= amount;
+            self.storage_accounts.insert(&account_id, &account_storage);
+            Promise::new(account_id).transfer(amount);
+        }
+
+        account_storage.to_storage_balance()
+    }
+
+    /// Unregisters the caller, refunding its deposit. Accounts with storage in use, i.e. which
+    /// pledged in epochs not archived yet, cannot be unregistered, even with `force`
+    #[payable]
+    fn storage_unregister(&mut self, force: Option<bool>) -> bool {
+        assert_one_yocto();
+        let account_id = env::predecessor_account_id();
+
+        let account_storage = match self.storage_accounts.get(&account_id) {
+            Some(account_storage) => account_storage,
+            None => return false,
+        };
+
+        if account_storage.used > self.account_storage_usage {
+            env::panic_str(if force.unwrap_or(false) {
+                "MetaDaoContract::storage_unregister::Force unregistration is not supported"
+            } else {
+                "MetaDaoContract::storage_unregister::The account has storage in use"
+            });
+        }
+
+        self.storage_accounts.remove(&account_id);
+
+        if account_storage.deposit > 0 {
+            Promise::new(account_id).transfer(account_storage.deposit);
+        }
+
+        true
+    }
+
+    fn storage_balance_bounds(&self) -> StorageBalanceBounds {
+        StorageBalanceBounds {
+            min: U128(self.account_storage_usage as Balance * env::STORAGE_PRICE_PER_BYTE),
+            max: None,
+        }
+    }
+
+    fn storage_balance_of(&self, account_id: AccountId) -> Option<StorageBalance> {
+        self.storage_accounts
+            .get(&account_id)
+            .map(|account_storage| account_storage.to_storage_balance())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::{get_allowed_registry_metadata, get_context};
+    use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
+    use near_contract_standards::non_fungible_token::enumeration::NonFungibleTokenEnumeration;
+    use near_sdk::test_utils::accounts;
+    use near_sdk::testing_env;
+
+    #[test]
+    fn test_storage_deposit_and_withdraw_works() {
+        testing_env!(get_context(accounts(2), parse_near!("0.1 N")));
+
+        let mut contract = MetaDaoContract::new(accounts(1));
+        let min_balance = contract.storage_balance_bounds().min.0;
+
+        assert!(contract.storage_balance_of(accounts(2)).is_none());
+
+        let storage_balance = contract.storage_deposit(None, Some(true));
+        assert_eq!(storage_balance.total.0, min_balance);
+        assert_eq!(storage_balance.available.0, 0);
+
+        let storage_balance = contract.storage_deposit(Some(accounts(2)), None);
+        assert_eq!(storage_balance.total.0, min_balance + parse_near!("0.1 N"));
+        assert_eq!(storage_balance.available.0, parse_near!("0.1 N"));
+
+        testing_env!(get_context(accounts(2), 1));
+
+        let storage_balance = contract.storage_withdraw(Some(U128(parse_near!("0.04 N"))));
+        assert_eq!(storage_balance.available.0, parse_near!("0.06 N"));
+
+        let storage_balance = contract.storage_withdraw(None);
+        assert_eq!(storage_balance.total.0, min_balance);
+        assert_eq!(storage_balance.available.0, 0);
+
+        assert!(contract.storage_unregister(None));
+        assert!(!contract.storage_unregister(None));
+        assert!(contract.storage_balance_of(accounts(2)).is_none());
+    }
+
+    #[test]
+    fn test_archive_epoch_releases_backers_storage() {
+        testing_env!(get_context(accounts(1), parse_near!("0.1 N")));
+
+        let mut contract = MetaDaoContract::new(accounts(1));
+        let min_balance = contract.storage_balance_bounds().min.0;
+
+        contract
+            .create_new_epoch(
+                Some(HashMap::from([(
+                    "wrap.near".to_string().try_into().unwrap(),
+                    500,
+                )])),
+                None,
+            )
+            .unwrap();
+        contract.set_registration().unwrap();
+        contract
+            .creator_registration(get_allowed_registry_metadata(&contract), None)
+            .unwrap();
+        contract.set_funding().unwrap();
+        contract.storage_deposit(Some(accounts(2)), None);
+
+        testing_env!(get_context("wrap.near".parse().unwrap(), 0));
+        contract.ft_on_transfer(accounts(2), U128(100), format!("{}_common", accounts(1)));
+
+        let storage_balance = contract.storage_balance_of(accounts(2)).unwrap();
+        assert!(storage_balance.available.0 < parse_near!("0.1 N") - min_balance);
+
+        testing_env!(get_context(accounts(1), parse_near!("0.1 N")));
+
+        // the epoch has to be over, and all its entries settled
+        assert!(matches!(
+            contract.archive_epoch(Epoch(1)),
+            Err(MetaDaoError::EpochCannotBeArchived)
+        ));
+
+        contract.set_minting().unwrap();
+        contract.end_epoch().unwrap();
+
+        assert!(matches!(
+            contract.archive_epoch(Epoch(1)),
+            Err(MetaDaoError::EpochCannotBeArchived)
+        ));
+
+        contract
+            .release_creator_payout(Epoch(1), accounts(1), None, None)
+            .unwrap();
+
+        // the NFT was not claimed by its backer
+        assert!(matches!(
+            contract.archive_epoch(Epoch(1)),
+            Err(MetaDaoError::EpochCannotBeArchived)
+        ));
+
+        testing_env!(get_context(accounts(2), parse_near!("0.1 N")));
+        assert!(matches!(
+            contract.settle_creator(Epoch(1), accounts(1), None, None),
+            Err(MetaDaoError::InvalidSettlementCall)
+        ));
+
+        testing_env!(get_context(accounts(1), parse_near!("0.1 N")));
+        assert_eq!(
+            contract
+                .settle_creator(Epoch(1), accounts(1), None, None)
+                .unwrap(),
+            None
+        );
+        assert_eq!(contract.nft_supply_for_owner(accounts(2)).0, 1);
+
+        testing_env!(get_context(accounts(2), 0));
+        assert!(matches!(
+            contract.archive_epoch(Epoch(1)),
+            Err(MetaDaoError::InvalidAdminCall)
+        ));
+
+        testing_env!(get_context(accounts(1), 0));
+        contract.archive_epoch(Epoch(1)).unwrap();
+
+        let storage_balance = contract.storage_balance_of(accounts(2)).unwrap();
+        assert_eq!(storage_balance.total.0, parse_near!("0.1 N"));
+        assert_eq!(
+            storage_balance.available.0,
+            parse_near!("0.1 N") - min_balance
+        );
+
+        assert!(contract.creator_funding.get(&Epoch(1)).is_none());
+        assert!(contract.user_funds.get(&Epoch(1)).is_none());
+        assert!(matches!(
+            contract.archive_epoch(Epoch(1)),
+            Err(MetaDaoError::EpochCannotBeArchived)
+        ));
+    }
+}
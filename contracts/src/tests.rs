@@ -10,67 +10,187 @@
 // use near_primitives::borsh::de;
 
 use crate::{
-    nft::{
-        CreatorNFTCopies, CreatorNFTDescription, CreatorNFTExtra, CreatorNFTMedia,
-        CreatorNFTRanking, CreatorNFTReference, CreatorNFTTitle,
-    },
+    nft::UserNFTRank,
+    registry::{LegacyCreatorMetadata, LegacyTierValue, TierConfig},
     *,
 };
+use near_sdk::test_utils::{accounts, VMContextBuilder};
+use near_sdk::{
+    testing_env, Balance, Gas, MockedBlockchain, PromiseResult, RuntimeFeesConfig, VMConfig,
+    VMContext,
+};
+
+/// utility function for testing callbacks logic
+pub fn testing_env_with_promise_results(context: VMContext, promise_results: Vec<PromiseResult>) {
+    near_sdk::env::set_blockchain_interface(MockedBlockchain::new(
+        context,
+        VMConfig::test(),
+        RuntimeFeesConfig::test(),
+        promise_results,
+        Default::default(),
+        Default::default(),
+        None,
+    ));
+}
+
+pub fn to_yocto(value: &str) -> u128 {
+    let vals: Vec<_> = value.split('.').collect();
+    let part1 = vals[0].parse::<u128>().unwrap() * 10u128.pow(24);
+    if vals.len() > 1 {
+        let power = vals[1].len() as u32;
+        let part2 = vals[1].parse::<u128>().unwrap() * 10u128.pow(24 - power);
+        part1 + part2
+    } else {
+        part1
+    }
+}
+
+pub fn get_context(predecessor: AccountId, deposit: Balance) -> VMContext {
+    let contract_account_id: AccountId = "conliq.testnet".to_string().try_into().unwrap();
+
+    VMContextBuilder::new()
+        .current_account_id(contract_account_id)
+        .signer_account_id(predecessor.clone())
+        .predecessor_account_id(predecessor)
+        .prepaid_gas(Gas(300 * 10u64.pow(16)))
+        .attached_deposit(deposit)
+        .build()
+}
+
+/// Returns a contract administered by `admin`, in the funding phase of its first epoch, which
+/// allows `wrap.near` and `usn`, with `accounts(1)` registered as creator
+pub fn get_contract_in_funding(admin: AccountId) -> MetaDaoContract {
+    testing_env!(get_context(admin.clone(), parse_near!("0.1 N")));
+
+    let mut contract = MetaDaoContract::new(admin.clone());
+
+    let mut protocol_accounts_fees = HashMap::<FTAccountId, u16>::new();
+
+    protocol_accounts_fees.insert("wrap.near".to_string().try_into().unwrap(), 500);
+    protocol_accounts_fees.insert("usn".to_string().try_into().unwrap(), 300);
+
+    contract
+        .create_new_epoch(Some(protocol_accounts_fees), None)
+        .unwrap();
+
+    contract.set_registration().unwrap();
+
+    testing_env!(get_context(accounts(1), parse_near!("0.1 N")));
+    contract
+        .creator_registration(get_allowed_registry_metadata(&contract), None)
+        .unwrap();
+
+    testing_env!(get_context(admin, parse_near!("0.1 N")));
+    contract.set_funding().unwrap();
+
+    contract
+}
+
+/// Records the pledges of `(user, nft_rank, ft_token_id, amount)` to `accounts(1)`
+pub fn fund_creator(contract: &mut MetaDaoContract, pledges: &[(&str, UserNFTRank, &str, u128)]) {
+    for (user, nft_rank, ft_token_id, amount) in pledges {
+        contract
+            .user_funding_creator(
+                user.to_string().try_into().unwrap(),
+                accounts(1),
+                nft_rank.clone(),
+                *amount,
+                ft_token_id.to_string().try_into().unwrap(),
+            )
+            .unwrap();
+    }
+}
+
+pub fn get_legacy_registry_metadata() -> LegacyCreatorMetadata {
+    LegacyCreatorMetadata {
+        nft_ranks: vec![
+            LegacyTierValue::Common(HashMap::<FTAccountId, u128>::from_iter([(
+                "wrap.near".to_string().try_into().unwrap(),
+                100_u128,
+            )])),
+            LegacyTierValue::Uncommon(HashMap::<FTAccountId, u128>::from_iter([(
+                "wrap.near".to_string().try_into().unwrap(),
+                250_u128,
+            )])),
+            LegacyTierValue::Rare(HashMap::<FTAccountId, u128>::from_iter([(
+                "wrap.near".to_string().try_into().unwrap(),
+                500_u128,
+            )])),
+        ],
+        titles: vec![
+            LegacyTierValue::Common("common".to_string()),
+            LegacyTierValue::Uncommon("uncommon".to_string()),
+            LegacyTierValue::Rare("rare".to_string()),
+        ],
+        descriptions: vec![
+            LegacyTierValue::Common("common".to_string()),
+            LegacyTierValue::Uncommon("uncommon".to_string()),
+            LegacyTierValue::Rare("rare".to_string()),
+        ],
+        medias: vec![
+            LegacyTierValue::Common("ipfs://media_common".to_string()),
+            LegacyTierValue::Uncommon("ipfs://media_uncommon".to_string()),
+            LegacyTierValue::Rare("ipfs://media_rare".to_string()),
+        ],
+        copies: vec![
+            LegacyTierValue::Common(100_u64),
+            LegacyTierValue::Uncommon(50_u64),
+            LegacyTierValue::Rare(5_u64),
+        ],
+        extras: vec![
+            LegacyTierValue::Common("extra_common".to_string()),
+            LegacyTierValue::Uncommon("extra_uncommon".to_string()),
+            LegacyTierValue::Rare("extra_rare".to_string()),
+        ],
+        references: vec![
+            LegacyTierValue::Common(None),
+            LegacyTierValue::Uncommon(None),
+            LegacyTierValue::Rare(None),
+        ],
+    }
+}
 
 pub fn get_registry_metadata() -> CreatorMetadata {
-    let nft_ranks = vec![
-        CreatorNFTRanking::Common(HashMap::<FTAccountId, u128>::from_iter([
-            ("wrap.near".to_string().try_into().unwrap(), 100_u128),
-            ("usn".to_string().try_into().unwrap(), 1_000_u128),
-        ])),
-        CreatorNFTRanking::Uncommon(HashMap::<FTAccountId, u128>::from_iter([
-            ("wrap.near".to_string().try_into().unwrap(), 250_u128),
-            ("usn".to_string().try_into().unwrap(), 2_500_u128),
-        ])),
-        CreatorNFTRanking::Rare(HashMap::<FTAccountId, u128>::from_iter([
-            ("wrap.near".to_string().try_into().unwrap(), 500_u128),
-            ("usn".to_string().try_into().unwrap(), 5_000_u128),
-        ])),
-    ];
-    let titles = vec![
-        CreatorNFTTitle::Common("common".to_string()),
-        CreatorNFTTitle::Uncommon("uncommon".to_string()),
-        CreatorNFTTitle::Rare("rare".to_string()),
-    ];
-    let descriptions = vec![
-        CreatorNFTDescription::Common("common".to_string()),
-        CreatorNFTDescription::Uncommon("uncommon".to_string()),
-        CreatorNFTDescription::Rare("rare".to_string()),
-    ];
-    let medias = vec![
-        CreatorNFTMedia::Common("media_common".to_string()),
-        CreatorNFTMedia::Uncommon("media_uncommon".to_string()),
-        CreatorNFTMedia::Rare("media_rare".to_string()),
-    ];
-    let copies = vec![
-        CreatorNFTCopies::Common(100_u64),
-        CreatorNFTCopies::Uncommon(50_u64),
-        CreatorNFTCopies::Rare(5_u64),
-    ];
-    let extras = vec![
-        CreatorNFTExtra::Common("extra_common".to_string()),
-        CreatorNFTExtra::Uncommon("extra_uncommon".to_string()),
-        CreatorNFTExtra::Rare("extra_rare".to_string()),
-    ];
-    let references = vec![
-        CreatorNFTReference::Common(None),
-        CreatorNFTReference::Uncommon(None),
-        CreatorNFTReference::Rare(None),
-    ];
-    CreatorMetadata::new(
-        nft_ranks,
-        titles,
-        descriptions,
-        medias,
+    let tier = |nft_rank: UserNFTRank, name: &str, price: u128, copies: u64| TierConfig {
+        nft_rank,
+        prices: HashMap::<FTAccountId, u128>::from_iter([
+            ("wrap.near".to_string().try_into().unwrap(), price),
+            ("usn".to_string().try_into().unwrap(), price * 10),
+            (near_token_id(), price * parse_near!("0.01 N")),
+        ]),
+        title: name.to_string(),
+        description: name.to_string(),
+        media: format!("ipfs://media_{}", name),
         copies,
-        extras,
-        references,
-    )
+        extra: format!("extra_{}", name),
+        reference: None,
+    };
+
+    CreatorMetadata::new(vec![
+        tier(UserNFTRank::Common, "common", 100, 100),
+        tier(UserNFTRank::Uncommon, "uncommon", 250, 50),
+        tier(UserNFTRank::Rare, "rare", 500, 5),
+    ])
+}
+
+/// Returns [`get_registry_metadata`], priced only in the tokens `contract` allows
+pub fn get_allowed_registry_metadata(contract: &MetaDaoContract) -> CreatorMetadata {
+    let tiers = get_registry_metadata()
+        .get_tiers()
+        .iter()
+        .cloned()
+        .map(|mut tier| {
+            tier.prices.retain(|ft_token_id, _| {
+                contract
+                    .protocol_allowed_tokens_fees
+                    .get(ft_token_id)
+                    .is_some()
+            });
+            tier
+        })
+        .collect();
+
+    CreatorMetadata::new(tiers)
 }
 
 // pub async fn init() -> anyhow::Result<(
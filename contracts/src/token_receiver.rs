@@ -1,78 +1,166 @@
 use near_contract_standards::fungible_token::receiver::FungibleTokenReceiver;
 use near_sdk::json_types::U128;
-use near_sdk::{env, near_bindgen, PromiseOrValue};
+use near_sdk::{env, near_bindgen, Promise, PromiseOrValue, StorageUsage};
 
-use crate::*;
+use crate::{
+    consts::{MAX_PLEDGE_ENTRY_STORAGE_USAGE, MAX_PLEDGE_STORAGE_USAGE},
+    error::MetaDaoError,
+    events::PledgeRejectionLog,
+    pledge::PledgeMessage,
+    *,
+};
 
 #[near_bindgen]
-impl MetaDaoContract {}
-
-#[near_bindgen]
-impl FungibleTokenReceiver for MetaDaoContract {
+impl MetaDaoContract {
+    /// Pledges the attached NEAR to `creator_account_id`, for an NFT of tier `rank`.
+    /// Any amount above the NFT price is returned to the caller
     #[payable]
-    fn ft_on_transfer(
+    #[handle_result]
+    pub fn pledge_near(
         &mut self,
-        sender_id: AccountId,
-        amount: near_sdk::json_types::U128,
-        msg: String,
-    ) -> PromiseOrValue<near_sdk::json_types::U128> {
-        if !self.in_funding {
-            env::panic_str(
-                format!(
-                    "MetadaoContract::ft_contract: Funding is not currently open for epoch {}",
-                    self.epoch.count()
-                )
-                .as_str(),
-            );
+        creator_account_id: CreatorAccountId,
+        rank: UserNFTRank,
+    ) -> Result<(), MetaDaoError> {
+        let user_id = env::predecessor_account_id();
+        let amount = env::attached_deposit();
+
+        let used_amount = self.internal_pledge(
+            user_id.clone(),
+            near_token_id(),
+            amount,
+            PledgeMessage::new(creator_account_id, rank),
+        )?;
+
+        if amount > used_amount {
+            Promise::new(user_id).transfer(amount - used_amount);
         }
 
-        let ft_token_id = env::predecessor_account_id();
+        Ok(())
+    }
+}
 
-        let metadata = msg.split('_').collect::<Vec<_>>();
-        let creator_account_id = AccountId::try_from(metadata[0].to_string())
-            .expect("MetaDaoContract::ft_on_transfer: failed to parse creator account id");
+impl MetaDaoContract {
+    /// Records `pledge`, of `amount` of `ft_token_id`, returning the amount
+    /// actually used, i.e. the price of the pledged NFTs
+    pub fn internal_pledge(
+        &mut self,
+        sender_id: UserAccountId,
+        ft_token_id: FTAccountId,
+        amount: u128,
+        pledge: PledgeMessage,
+    ) -> Result<u128, MetaDaoError> {
+        if !self.is_in_funding() {
+            return Err(MetaDaoError::NotInFundingPeriod);
+        }
 
-        let amount = amount.0;
         let epoch = self.epoch;
 
-        let creators_metadata = self
+        if matches!(pledge.epoch, Some(pledge_epoch) if pledge_epoch != epoch) {
+            return Err(MetaDaoError::InvalidCurrentEpoch);
+        }
+
+        let creator_account_id = pledge.creator;
+
+        let creator_metadata = self
             .creators_metadata
             .get(&epoch)
-            .expect("MetaDaoContract::ft_on_transfer: Invalid epoch");
+            .ok_or(MetaDaoError::InvalidCurrentEpoch)?
+            .get(&creator_account_id)
+            .ok_or(MetaDaoError::CreatorIsNotRegistered)?;
 
-        let creator_metadata = creators_metadata.get(&creator_account_id).expect(
-            "MetaDaoContract::ft_on_transfer: Invalid creator account id for current epoch",
-        );
+        let user_nft_rank = pledge.rank;
 
-        let user_nft_rank = match metadata[1] {
-            "common" => UserNFTRank::Common,
-            "uncommon" => UserNFTRank::Uncommon,
-            "rare" => UserNFTRank::Rare,
-            _ => return PromiseOrValue::Value(U128(amount)),
-        };
+        let nft_price = creator_metadata
+            .tier(&user_nft_rank)?
+            .get_price(&ft_token_id)?;
 
-        let min_fund_amount = creator_metadata
-            .nft_rank(user_nft_rank.clone())
-            .get_amount_from_nft_rank(&ft_token_id)
-            .expect("ft_on_transfer::Invalid fungible token id");
+        let used_amount = nft_price
+            .checked_mul(pledge.quantity as u128)
+            .ok_or(MetaDaoError::InsufficientPledgeAmount)?;
 
-        if amount < min_fund_amount {
-            env::panic_str(
-                "ft_on_transfer::User did not provide enough funds to obtain the chosen NFT",
-            );
+        if amount < used_amount {
+            return Err(MetaDaoError::InsufficientPledgeAmount);
         }
 
-        // TODO: 1. assert that the user sent enough funds to buy the NFTs
-        // 2. return the value to the user, if transaction failed
-        match self.user_funding_creator(
-            sender_id,
-            creator_account_id,
-            user_nft_rank,
-            amount,
-            ft_token_id,
-        ) {
-            Err(_) => PromiseOrValue::Value(U128(amount)),
-            _ => PromiseOrValue::Value(U128(0)),
+        // the whole quantity has to be available, so that a pledge is never partially recorded
+        let available_copies = self
+            .get_tier_availability(&epoch, &creator_account_id)?
+            .get(&user_nft_rank)
+            .copied()
+            .unwrap_or(0);
+
+        if available_copies < pledge.quantity as u64 {
+            return Err(MetaDaoError::TierSoldOut);
+        }
+
+        // the storage balance is checked upfront, so that a pledge is either fully recorded, or rejected
+        self.assert_storage_is_covered(
+            &sender_id,
+            MAX_PLEDGE_STORAGE_USAGE
+                + MAX_PLEDGE_ENTRY_STORAGE_USAGE * pledge.quantity as StorageUsage,
+        )?;
+
+        let initial_storage_usage = env::storage_usage();
+
+        // each NFT is funded by its own entry, of the tier price. From here on state is written,
+        // so a failure panics, rolling the pledge back instead of recording it for free
+        let result = (0..pledge.quantity)
+            .try_for_each(|_| {
+                self.user_funding_creator(
+                    sender_id.clone(),
+                    creator_account_id.clone(),
+                    user_nft_rank.clone(),
+                    nft_price,
+                    ft_token_id.clone(),
+                )
+            })
+            .and_then(|_| self.charge_storage(&sender_id, initial_storage_usage));
+
+        if let Err(error) = result {
+            env::panic_str(&format!("MetaDaoContract::internal_pledge::{}", error));
+        }
+
+        Ok(used_amount)
+    }
+}
+
+#[near_bindgen]
+impl FungibleTokenReceiver for MetaDaoContract {
+    /// Pledges the transferred tokens, following `msg`. Rejected pledges are logged,
+    /// and the whole `amount` is returned to `sender_id`. Otherwise, only the amount
+    /// exceeding the price of the pledged NFTs is returned
+    #[payable]
+    fn ft_on_transfer(
+        &mut self,
+        sender_id: AccountId,
+        amount: near_sdk::json_types::U128,
+        msg: String,
+    ) -> PromiseOrValue<near_sdk::json_types::U128> {
+        let ft_token_id = env::predecessor_account_id();
+
+        // native NEAR can only be pledged via `pledge_near`
+        let pledge = if ft_token_id == near_token_id() {
+            Err(MetaDaoError::InvalidFTTokenId)
+        } else {
+            PledgeMessage::parse(&msg)
+        };
+
+        match pledge.and_then(|pledge| {
+            self.internal_pledge(sender_id.clone(), ft_token_id.clone(), amount.0, pledge)
+        }) {
+            Ok(used_amount) => PromiseOrValue::Value(U128(amount.0 - used_amount)),
+            Err(error) => {
+                MetaStageEvent::PledgeRejected(vec![PledgeRejectionLog {
+                    epoch: self.epoch.count(),
+                    user_id: sender_id,
+                    ft_token_id,
+                    amount,
+                    reason: error.to_string(),
+                }])
+                .emit();
+
+                PromiseOrValue::Value(amount)
+            }
         }
     }
 }
@@ -80,90 +168,110 @@ impl FungibleTokenReceiver for MetaDaoContract {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::consts::CREATOR_REGISTRY_STORAGE_COST;
-    use crate::tests::get_registry_metadata;
-    use near_sdk::test_utils::{accounts, VMContextBuilder};
-    use near_sdk::{
-        testing_env, AccountId, Gas, MockedBlockchain, PromiseResult, RuntimeFeesConfig, VMConfig,
-        VMContext,
-    };
+    use crate::tests::{get_allowed_registry_metadata, get_context, to_yocto};
+    use near_contract_standards::storage_management::StorageManagement;
+    use near_sdk::test_utils::{accounts, get_logs};
+    use near_sdk::{testing_env, AccountId, VMContext};
     use std::collections::HashMap;
     use std::convert::TryInto;
 
-    /// utility function for testing callbacks logic
-    #[allow(dead_code)]
-    pub fn testing_env_with_promise_results(
-        context: VMContext,
-        promise_results: Vec<PromiseResult>,
-    ) {
-        near_sdk::env::set_blockchain_interface(MockedBlockchain::new(
-            context,
-            VMConfig::test(),
-            RuntimeFeesConfig::test(),
-            promise_results,
-            Default::default(),
-            Default::default(),
-            None,
+    /// Deposits storage for `account_id`, restoring `context` afterwards
+    fn deposit_storage(contract: &mut MetaDaoContract, account_id: AccountId, context: VMContext) {
+        testing_env!(get_context(
+            "wrap.near".parse().unwrap(),
+            parse_near!("0.1 N")
         ));
+        contract.storage_deposit(Some(account_id), None);
+        testing_env!(context);
     }
 
-    pub fn to_yocto(value: &str) -> u128 {
-        let vals: Vec<_> = value.split('.').collect();
-        let part1 = vals[0].parse::<u128>().unwrap() * 10u128.pow(24);
-        if vals.len() > 1 {
-            let power = vals[1].len() as u32;
-            let part2 = vals[1].parse::<u128>().unwrap() * 10u128.pow(24 - power);
-            part1 + part2
-        } else {
-            part1
-        }
-    }
+    #[test]
+    fn test_ft_on_transfer_is_successful() {
+        let account: AccountId = "wrap.near".to_string().try_into().unwrap();
+        let admin = account.clone();
 
-    fn get_context_with_storage(storage: u128) -> VMContext {
-        let contract_account_id: AccountId = "conliq.testnet".to_string().try_into().unwrap();
+        let storage = parse_near!("0.1 N");
 
-        let account: AccountId = "wrap.near".to_string().try_into().unwrap();
+        let context = get_context("wrap.near".parse().unwrap(), storage);
+        testing_env!(context.clone());
+
+        let sender_id = accounts(2);
+        let amount = U128(1_000_000_000_u128);
+        let msg = format!("{}_common", account.clone(),);
+
+        let mut contract = MetaDaoContract::new(admin);
 
-        VMContextBuilder::new()
-            .current_account_id(contract_account_id)
-            .attached_deposit(to_yocto("1000"))
-            .signer_account_id(account.clone())
-            .predecessor_account_id(account)
-            .prepaid_gas(Gas(300 * 10u64.pow(16)))
-            .attached_deposit(storage)
-            .build()
+        let mut protocol_tokens_fees = HashMap::new();
+
+        protocol_tokens_fees.insert("wrap.near".to_string().try_into().unwrap(), 10);
+        protocol_tokens_fees.insert("usn".to_string().try_into().unwrap(), 5);
+
+        contract
+            .create_new_epoch(Some(protocol_tokens_fees), None)
+            .unwrap();
+
+        contract.set_registration().unwrap();
+
+        let metadata = get_allowed_registry_metadata(&contract);
+
+        contract.creator_registration(metadata, None).unwrap();
+
+        assert!(contract
+            .creators_per_epoch_set
+            .get(&Epoch(1u16))
+            .unwrap()
+            .contains(&account));
+
+        contract.set_funding().unwrap();
+        deposit_storage(&mut contract, sender_id.clone(), context);
+
+        let amount = contract.ft_on_transfer(sender_id.clone(), amount, msg.to_string());
+        assert!(matches!(
+            amount,
+            PromiseOrValue::Value(U128(999_999_900_u128))
+        ));
+
+        // the pledge storage is charged to the sender
+        let storage_balance = contract.storage_balance_of(sender_id).unwrap();
+        assert!(
+            storage_balance.available.0
+                < storage_balance.total.0 - contract.storage_balance_bounds().min.0
+        );
     }
 
     #[test]
-    fn test_ft_on_transfer_is_successful() {
+    fn test_ft_on_transfer_with_json_message_is_successful() {
         let account: AccountId = "wrap.near".to_string().try_into().unwrap();
         let admin = account.clone();
 
-        let storage = (CREATOR_REGISTRY_STORAGE_COST as u128) * env::STORAGE_PRICE_PER_BYTE;
+        let storage = parse_near!("0.1 N");
 
-        let context = get_context_with_storage(storage);
+        let context = get_context("wrap.near".parse().unwrap(), storage);
         testing_env!(context.clone());
 
         let sender_id = accounts(2);
-        let amount = U128(1_000_000_000_u128);
-        let msg = format!("{}_common", account.clone(),);
+        let amount = U128(1_001_u128);
+        let msg = format!(
+            r#"{{"version":1,"action":"pledge","creator":"{}","rank":"rare","epoch":1,"quantity":2}}"#,
+            account
+        );
 
         let mut contract = MetaDaoContract::new(admin);
 
         let mut protocol_tokens_fees = HashMap::new();
 
-        protocol_tokens_fees.insert("wrap.near".to_string().try_into().unwrap(), 0.001);
-        protocol_tokens_fees.insert("usn".to_string().try_into().unwrap(), 0.0005);
+        protocol_tokens_fees.insert("wrap.near".to_string().try_into().unwrap(), 10);
+        protocol_tokens_fees.insert("usn".to_string().try_into().unwrap(), 5);
 
         contract
-            .create_new_epoch(Some(protocol_tokens_fees))
+            .create_new_epoch(Some(protocol_tokens_fees), None)
             .unwrap();
 
         contract.set_registration().unwrap();
 
-        let metadata = get_registry_metadata();
+        let metadata = get_allowed_registry_metadata(&contract);
 
-        contract.creator_registration(metadata).unwrap();
+        contract.creator_registration(metadata, None).unwrap();
 
         assert!(contract
             .creators_per_epoch_set
@@ -172,22 +280,34 @@ mod tests {
             .contains(&account));
 
         contract.set_funding().unwrap();
+        deposit_storage(&mut contract, sender_id.clone(), context);
 
         let amount = contract.ft_on_transfer(sender_id, amount, msg.to_string());
-        assert!(matches!(amount, PromiseOrValue::Value(U128(0_u128))));
+        assert!(matches!(amount, PromiseOrValue::Value(U128(1_u128))));
+
+        let creator_funding = contract
+            .creator_funding
+            .get(&Epoch(1u16))
+            .unwrap()
+            .get(&account)
+            .unwrap();
+
+        assert_eq!(creator_funding.len(), 2);
+        assert_eq!(creator_funding[0].amount, 500_u128);
+        assert_eq!(creator_funding[1].amount, 500_u128);
+        assert!(creator_funding
+            .iter()
+            .all(|funding| funding.nft_rank == UserNFTRank::Rare));
     }
 
     #[test]
-    #[should_panic(
-        expected = "MetadaoContract::ft_contract: Funding is not currently open for epoch 1"
-    )]
-    fn test_ft_on_transfer_panics_if_not_in_funding() {
+    fn test_ft_on_transfer_refunds_if_not_in_funding() {
         let account: AccountId = "wrap.near".to_string().try_into().unwrap();
         let admin = account.clone();
 
-        let storage = (CREATOR_REGISTRY_STORAGE_COST as u128) * env::STORAGE_PRICE_PER_BYTE;
+        let storage = parse_near!("0.1 N");
 
-        let context = get_context_with_storage(storage);
+        let context = get_context("wrap.near".parse().unwrap(), storage);
         testing_env!(context.clone());
 
         let sender_id = accounts(2);
@@ -198,18 +318,18 @@ mod tests {
 
         let mut protocol_tokens_fees = HashMap::new();
 
-        protocol_tokens_fees.insert("wrap.near".to_string().try_into().unwrap(), 0.001);
-        protocol_tokens_fees.insert("usn".to_string().try_into().unwrap(), 0.0005);
+        protocol_tokens_fees.insert("wrap.near".to_string().try_into().unwrap(), 10);
+        protocol_tokens_fees.insert("usn".to_string().try_into().unwrap(), 5);
 
         contract
-            .create_new_epoch(Some(protocol_tokens_fees))
+            .create_new_epoch(Some(protocol_tokens_fees), None)
             .unwrap();
 
         contract.set_registration().unwrap();
 
-        let metadata = get_registry_metadata();
+        let metadata = get_allowed_registry_metadata(&contract);
 
-        contract.creator_registration(metadata).unwrap();
+        contract.creator_registration(metadata, None).unwrap();
 
         assert!(contract
             .creators_per_epoch_set
@@ -217,20 +337,25 @@ mod tests {
             .unwrap()
             .contains(&account));
 
-        contract.ft_on_transfer(sender_id, amount, msg.to_string());
+        let refund = contract.ft_on_transfer(sender_id, amount, msg.to_string());
+        assert!(matches!(
+            refund,
+            PromiseOrValue::Value(U128(1_000_000_000_u128))
+        ));
+        assert!(get_logs()
+            .last()
+            .unwrap()
+            .contains(r#""reason":"Not in funding period""#));
     }
 
     #[test]
-    #[should_panic(
-        expected = "MetaDaoContract::ft_on_transfer: failed to parse creator account id"
-    )]
-    fn test_ft_on_transfer_panics_if_cannot_parse_account_id() {
+    fn test_ft_on_transfer_refunds_if_cannot_parse_account_id() {
         let account: AccountId = "wrap.near".to_string().try_into().unwrap();
         let admin = account.clone();
 
-        let storage = (CREATOR_REGISTRY_STORAGE_COST as u128) * env::STORAGE_PRICE_PER_BYTE;
+        let storage = parse_near!("0.1 N");
 
-        let context = get_context_with_storage(storage);
+        let context = get_context("wrap.near".parse().unwrap(), storage);
         testing_env!(context.clone());
 
         let sender_id = accounts(2);
@@ -241,18 +366,18 @@ mod tests {
 
         let mut protocol_tokens_fees = HashMap::new();
 
-        protocol_tokens_fees.insert("wrap.near".to_string().try_into().unwrap(), 0.001);
-        protocol_tokens_fees.insert("usn".to_string().try_into().unwrap(), 0.0005);
+        protocol_tokens_fees.insert("wrap.near".to_string().try_into().unwrap(), 10);
+        protocol_tokens_fees.insert("usn".to_string().try_into().unwrap(), 5);
 
         contract
-            .create_new_epoch(Some(protocol_tokens_fees))
+            .create_new_epoch(Some(protocol_tokens_fees), None)
             .unwrap();
 
         contract.set_registration().unwrap();
 
-        let metadata = get_registry_metadata();
+        let metadata = get_allowed_registry_metadata(&contract);
 
-        contract.creator_registration(metadata).unwrap();
+        contract.creator_registration(metadata, None).unwrap();
 
         assert!(contract
             .creators_per_epoch_set
@@ -262,20 +387,29 @@ mod tests {
 
         contract.set_funding().unwrap();
 
-        contract.ft_on_transfer(sender_id, amount, msg.to_string());
+        let refund = contract.ft_on_transfer(sender_id, amount, msg.to_string());
+        assert!(matches!(
+            refund,
+            PromiseOrValue::Value(U128(1_000_000_000_u128))
+        ));
+        assert!(get_logs()
+            .last()
+            .unwrap()
+            .contains(r#""event":"pledge_rejected""#));
+        assert!(get_logs()
+            .last()
+            .unwrap()
+            .contains(r#""reason":"Invalid pledge message""#));
     }
 
     #[test]
-    #[should_panic(
-        expected = "MetaDaoContract::ft_on_transfer: Invalid creator account id for current epoch"
-    )]
-    fn test_ft_on_transfer_panics_if_invalid_creator_account_id() {
+    fn test_ft_on_transfer_refunds_if_invalid_creator_account_id() {
         let account: AccountId = "wrap.near".to_string().try_into().unwrap();
         let admin = account.clone();
 
-        let storage = (CREATOR_REGISTRY_STORAGE_COST as u128) * env::STORAGE_PRICE_PER_BYTE;
+        let storage = parse_near!("0.1 N");
 
-        let context = get_context_with_storage(storage);
+        let context = get_context("wrap.near".parse().unwrap(), storage);
         testing_env!(context.clone());
 
         let sender_id = accounts(2);
@@ -286,18 +420,18 @@ mod tests {
 
         let mut protocol_tokens_fees = HashMap::new();
 
-        protocol_tokens_fees.insert("wrap.near".to_string().try_into().unwrap(), 0.001);
-        protocol_tokens_fees.insert("usn".to_string().try_into().unwrap(), 0.0005);
+        protocol_tokens_fees.insert("wrap.near".to_string().try_into().unwrap(), 10);
+        protocol_tokens_fees.insert("usn".to_string().try_into().unwrap(), 5);
 
         contract
-            .create_new_epoch(Some(protocol_tokens_fees))
+            .create_new_epoch(Some(protocol_tokens_fees), None)
             .unwrap();
 
         contract.set_registration().unwrap();
 
-        let metadata = get_registry_metadata();
+        let metadata = get_allowed_registry_metadata(&contract);
 
-        contract.creator_registration(metadata).unwrap();
+        contract.creator_registration(metadata, None).unwrap();
 
         assert!(contract
             .creators_per_epoch_set
@@ -307,40 +441,50 @@ mod tests {
 
         contract.set_funding().unwrap();
 
-        contract.ft_on_transfer(sender_id, amount, msg.to_string());
+        let refund = contract.ft_on_transfer(sender_id, amount, msg.to_string());
+        assert!(matches!(
+            refund,
+            PromiseOrValue::Value(U128(1_000_000_000_u128))
+        ));
+        assert!(get_logs()
+            .last()
+            .unwrap()
+            .contains(r#""reason":"Creator is not registered for current epoch""#));
     }
 
     #[test]
-    #[should_panic(expected = "MetaDaoContract::ft_on_transfer: Invalid epoch")]
-    fn test_ft_on_transfer_panics_if_invalid_epoch() {
+    fn test_ft_on_transfer_refunds_if_invalid_epoch() {
         let account: AccountId = "wrap.near".to_string().try_into().unwrap();
         let admin = account.clone();
 
-        let storage = (CREATOR_REGISTRY_STORAGE_COST as u128) * env::STORAGE_PRICE_PER_BYTE;
+        let storage = parse_near!("0.1 N");
 
-        let context = get_context_with_storage(storage);
+        let context = get_context("wrap.near".parse().unwrap(), storage);
         testing_env!(context.clone());
 
         let sender_id = accounts(2);
         let amount = U128(1_000_000_000_u128);
-        let msg = format!("lotus_common",);
+        let msg = format!(
+            r#"{{"action":"pledge","creator":"{}","rank":"common","epoch":2}}"#,
+            account
+        );
 
         let mut contract = MetaDaoContract::new(admin);
 
         let mut protocol_tokens_fees = HashMap::new();
 
-        protocol_tokens_fees.insert("wrap.near".to_string().try_into().unwrap(), 0.001);
-        protocol_tokens_fees.insert("usn".to_string().try_into().unwrap(), 0.0005);
+        protocol_tokens_fees.insert("wrap.near".to_string().try_into().unwrap(), 10);
+        protocol_tokens_fees.insert("usn".to_string().try_into().unwrap(), 5);
 
         contract
-            .create_new_epoch(Some(protocol_tokens_fees))
+            .create_new_epoch(Some(protocol_tokens_fees), None)
             .unwrap();
 
         contract.set_registration().unwrap();
 
-        let metadata = get_registry_metadata();
+        let metadata = get_allowed_registry_metadata(&contract);
 
-        contract.creator_registration(metadata).unwrap();
+        contract.creator_registration(metadata, None).unwrap();
 
         assert!(contract
             .creators_per_epoch_set
@@ -350,22 +494,25 @@ mod tests {
 
         contract.set_funding().unwrap();
 
-        contract.epoch = Epoch(0_u16);
-
-        contract.ft_on_transfer(sender_id, amount, msg.to_string());
+        let refund = contract.ft_on_transfer(sender_id, amount, msg.to_string());
+        assert!(matches!(
+            refund,
+            PromiseOrValue::Value(U128(1_000_000_000_u128))
+        ));
+        assert!(get_logs()
+            .last()
+            .unwrap()
+            .contains(r#""reason":"Invalid current epoch""#));
     }
 
     #[test]
-    #[should_panic(
-        expected = "MetaDaoContract::ft_on_transfer: Invalid creator account id for current epoch"
-    )]
-    fn test_ft_on_transfer_panics_if_invalid_ft_account_id() {
+    fn test_ft_on_transfer_refunds_if_invalid_ft_account_id() {
         let account: AccountId = "usn".to_string().try_into().unwrap();
         let admin = "wrap.near".to_string().try_into().unwrap();
 
-        let storage = (CREATOR_REGISTRY_STORAGE_COST as u128) * env::STORAGE_PRICE_PER_BYTE;
+        let storage = parse_near!("0.1 N");
 
-        let context = get_context_with_storage(storage);
+        let context = get_context("wrap.near".parse().unwrap(), storage);
         testing_env!(context.clone());
 
         let sender_id = accounts(2);
@@ -376,35 +523,40 @@ mod tests {
 
         let mut protocol_tokens_fees = HashMap::new();
 
-        protocol_tokens_fees.insert("wrap.near".to_string().try_into().unwrap(), 0.001);
-        protocol_tokens_fees.insert("usn".to_string().try_into().unwrap(), 0.0005);
+        protocol_tokens_fees.insert("wrap.near".to_string().try_into().unwrap(), 10);
+        protocol_tokens_fees.insert("usn".to_string().try_into().unwrap(), 5);
 
         contract
-            .create_new_epoch(Some(protocol_tokens_fees))
+            .create_new_epoch(Some(protocol_tokens_fees), None)
             .unwrap();
 
         contract.set_registration().unwrap();
 
-        let metadata = get_registry_metadata();
+        let metadata = get_allowed_registry_metadata(&contract);
 
-        contract.creator_registration(metadata).unwrap();
+        contract.creator_registration(metadata, None).unwrap();
 
         contract.set_funding().unwrap();
 
-        contract.ft_on_transfer(sender_id, amount, msg.to_string());
+        let refund = contract.ft_on_transfer(sender_id, amount, msg.to_string());
+        assert!(matches!(
+            refund,
+            PromiseOrValue::Value(U128(1_000_000_000_u128))
+        ));
+        assert!(get_logs()
+            .last()
+            .unwrap()
+            .contains(r#""reason":"Creator is not registered for current epoch""#));
     }
 
     #[test]
-    #[should_panic(
-        expected = "ft_on_transfer::User did not provide enough funds to obtain the chosen NFT"
-    )]
-    fn test_ft_on_transfer_panics_if_sender_did_not_provide_enough_amount() {
+    fn test_ft_on_transfer_refunds_if_sender_did_not_provide_enough_amount() {
         let account: AccountId = "wrap.near".to_string().try_into().unwrap();
         let admin = "wrap.near".to_string().try_into().unwrap();
 
-        let storage = (CREATOR_REGISTRY_STORAGE_COST as u128) * env::STORAGE_PRICE_PER_BYTE;
+        let storage = parse_near!("0.1 N");
 
-        let context = get_context_with_storage(storage);
+        let context = get_context("wrap.near".parse().unwrap(), storage);
         testing_env!(context.clone());
 
         let sender_id = accounts(2);
@@ -415,21 +567,239 @@ mod tests {
 
         let mut protocol_tokens_fees = HashMap::new();
 
-        protocol_tokens_fees.insert("wrap.near".to_string().try_into().unwrap(), 0.001);
-        protocol_tokens_fees.insert("usn".to_string().try_into().unwrap(), 0.0005);
+        protocol_tokens_fees.insert("wrap.near".to_string().try_into().unwrap(), 10);
+        protocol_tokens_fees.insert("usn".to_string().try_into().unwrap(), 5);
 
         contract
-            .create_new_epoch(Some(protocol_tokens_fees))
+            .create_new_epoch(Some(protocol_tokens_fees), None)
             .unwrap();
 
         contract.set_registration().unwrap();
 
-        let metadata = get_registry_metadata();
+        let metadata = get_allowed_registry_metadata(&contract);
 
-        contract.creator_registration(metadata).unwrap();
+        contract.creator_registration(metadata, None).unwrap();
 
         contract.set_funding().unwrap();
 
-        contract.ft_on_transfer(sender_id, amount, msg.to_string());
+        let refund = contract.ft_on_transfer(sender_id, amount, msg.to_string());
+        assert!(matches!(refund, PromiseOrValue::Value(U128(1_u128))));
+        assert!(get_logs()
+            .last()
+            .unwrap()
+            .contains(r#""reason":"User did not provide enough funds to obtain the chosen NFT""#));
+    }
+
+    #[test]
+    fn test_ft_on_transfer_refunds_if_storage_is_not_covered() {
+        let account: AccountId = "wrap.near".to_string().try_into().unwrap();
+
+        let context = get_context("wrap.near".parse().unwrap(), parse_near!("0.1 N"));
+        testing_env!(context.clone());
+
+        let sender_id = accounts(2);
+        let amount = U128(1_000_u128);
+        let msg = format!("{}_common", account);
+
+        let mut contract = MetaDaoContract::new(account);
+
+        let mut protocol_tokens_fees = HashMap::new();
+
+        protocol_tokens_fees.insert("wrap.near".to_string().try_into().unwrap(), 10);
+
+        contract
+            .create_new_epoch(Some(protocol_tokens_fees), None)
+            .unwrap();
+
+        contract.set_registration().unwrap();
+        contract
+            .creator_registration(get_allowed_registry_metadata(&contract), None)
+            .unwrap();
+        contract.set_funding().unwrap();
+
+        let refund = contract.ft_on_transfer(sender_id.clone(), amount, msg.to_string());
+        assert!(matches!(refund, PromiseOrValue::Value(U128(1_000_u128))));
+        assert!(get_logs()
+            .last()
+            .unwrap()
+            .contains(r#""reason":"Account storage balance does not cover the storage costs""#));
+
+        // the minimum storage balance does not cover a pledge
+        let min_balance = contract.storage_balance_bounds().min;
+        testing_env!(get_context("wrap.near".parse().unwrap(), min_balance.0));
+        contract.storage_deposit(Some(sender_id.clone()), None);
+        testing_env!(context);
+
+        let refund = contract.ft_on_transfer(sender_id, amount, msg);
+        assert!(matches!(refund, PromiseOrValue::Value(U128(1_000_u128))));
+        assert!(contract
+            .user_funds
+            .get(&Epoch(1))
+            .unwrap()
+            .get(&accounts(2))
+            .is_none());
+    }
+
+    #[test]
+    fn test_pledge_near_works() {
+        let account: AccountId = "wrap.near".to_string().try_into().unwrap();
+        let admin = account.clone();
+
+        let storage = parse_near!("0.1 N");
+
+        let context = get_context("wrap.near".parse().unwrap(), storage);
+        testing_env!(context.clone());
+
+        let mut contract = MetaDaoContract::new(admin);
+
+        let mut protocol_tokens_fees = HashMap::new();
+
+        protocol_tokens_fees.insert("wrap.near".to_string().try_into().unwrap(), 10);
+        protocol_tokens_fees.insert(near_token_id(), 10);
+
+        contract
+            .create_new_epoch(Some(protocol_tokens_fees), None)
+            .unwrap();
+
+        contract.set_registration().unwrap();
+
+        let metadata = get_allowed_registry_metadata(&contract);
+
+        contract.creator_registration(metadata, None).unwrap();
+
+        contract.set_funding().unwrap();
+        deposit_storage(&mut contract, accounts(2), context);
+
+        let mut context = get_context("wrap.near".parse().unwrap(), to_yocto("1.5"));
+        context.predecessor_account_id = accounts(2);
+        testing_env!(context);
+
+        contract
+            .pledge_near(account.clone(), UserNFTRank::Common)
+            .unwrap();
+
+        let creator_funding = contract
+            .creator_funding
+            .get(&Epoch(1u16))
+            .unwrap()
+            .get(&account)
+            .unwrap();
+
+        assert_eq!(creator_funding.len(), 1);
+        assert_eq!(creator_funding[0].user_id, accounts(2));
+        assert_eq!(creator_funding[0].ft_token_id, near_token_id());
+        assert_eq!(creator_funding[0].amount, to_yocto("1"));
+
+        let mut context = get_context("wrap.near".parse().unwrap(), to_yocto("0.5"));
+        context.predecessor_account_id = accounts(2);
+        testing_env!(context);
+
+        assert!(matches!(
+            contract.pledge_near(account, UserNFTRank::Common),
+            Err(MetaDaoError::InsufficientPledgeAmount)
+        ));
+    }
+
+    #[test]
+    fn test_ft_on_transfer_refunds_if_native_near_token_id() {
+        let account: AccountId = "wrap.near".to_string().try_into().unwrap();
+
+        let mut context = get_context("wrap.near".parse().unwrap(), parse_near!("0.1 N"));
+        context.predecessor_account_id = near_token_id();
+        testing_env!(context);
+
+        let mut contract = MetaDaoContract::new(account.clone());
+
+        let amount = U128(to_yocto("1"));
+        let msg = format!("{}_common", account);
+
+        let refund = contract.ft_on_transfer(accounts(2), amount, msg);
+        assert!(matches!(refund, PromiseOrValue::Value(U128(amount)) if amount == to_yocto("1")));
+        assert!(get_logs()
+            .last()
+            .unwrap()
+            .contains(r#""reason":"Invalid Fungible token id""#));
+    }
+
+    #[test]
+    fn test_ft_on_transfer_refunds_if_tier_is_sold_out() {
+        let account: AccountId = "wrap.near".to_string().try_into().unwrap();
+        let admin = account.clone();
+
+        let storage = parse_near!("0.1 N");
+
+        let context = get_context("wrap.near".parse().unwrap(), storage);
+        testing_env!(context.clone());
+
+        let sender_id = accounts(2);
+        let msg = |quantity: u32| {
+            format!(
+                r#"{{"version":1,"action":"pledge","creator":"{}","rank":"rare","quantity":{}}}"#,
+                account, quantity
+            )
+        };
+
+        let mut contract = MetaDaoContract::new(admin);
+
+        let mut protocol_tokens_fees = HashMap::new();
+
+        protocol_tokens_fees.insert("wrap.near".to_string().try_into().unwrap(), 10);
+
+        contract
+            .create_new_epoch(Some(protocol_tokens_fees), None)
+            .unwrap();
+
+        contract.set_registration().unwrap();
+
+        let metadata = get_allowed_registry_metadata(&contract);
+
+        contract.creator_registration(metadata, None).unwrap();
+
+        contract.set_funding().unwrap();
+        deposit_storage(&mut contract, sender_id.clone(), context);
+
+        // the rare tier has 5 copies
+        let refund = contract.ft_on_transfer(sender_id.clone(), U128(2_000_u128), msg(4));
+        assert!(matches!(refund, PromiseOrValue::Value(U128(0_u128))));
+
+        let refund = contract.ft_on_transfer(sender_id.clone(), U128(1_000_u128), msg(2));
+        assert!(matches!(refund, PromiseOrValue::Value(U128(1_000_u128))));
+        assert!(get_logs()
+            .last()
+            .unwrap()
+            .contains(r#""reason":"NFT tier is sold out""#));
+
+        let refund = contract.ft_on_transfer(sender_id, U128(500_u128), msg(1));
+        assert!(matches!(refund, PromiseOrValue::Value(U128(0_u128))));
+
+        let creator_funding = contract
+            .creator_funding
+            .get(&contract.epoch)
+            .unwrap()
+            .get(&account)
+            .unwrap();
+        assert_eq!(creator_funding.len(), 5);
+
+        assert_eq!(
+            contract
+                .tier_availability(contract.epoch, account.clone())
+                .unwrap(),
+            HashMap::from_iter([
+                (UserNFTRank::Common, 100),
+                (UserNFTRank::Uncommon, 50),
+                (UserNFTRank::Rare, 0),
+            ])
+        );
+
+        assert!(matches!(
+            contract.user_funding_creator(
+                accounts(3),
+                account,
+                UserNFTRank::Rare,
+                500_u128,
+                "wrap.near".to_string().try_into().unwrap(),
+            ),
+            Err(MetaDaoError::TierSoldOut)
+        ));
     }
 }
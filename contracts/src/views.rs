@@ -1,9 +1,11 @@
 use near_sdk::near_bindgen;
 
-use crate::{error::MetaDaoError, *};
+use crate::{error::MetaDaoError, fund_creators::CreatorBalanceView, *};
 
 #[near_bindgen]
 impl MetaDaoContract {
+    /// Returns the gross amount pledged to `creator_id` in the current epoch, summed over all
+    /// fungible tokens. See `creator_balances` for the net proceeds withdrawn, and claimable
     #[handle_result]
     pub fn creator_total_funds(&self, creator_id: CreatorAccountId) -> Result<u128, MetaDaoError> {
         let creator_funds_map = self
@@ -18,6 +20,42 @@ impl MetaDaoContract {
         }
     }
 
+    pub fn creator_funding_goal(
+        &self,
+        epoch: Epoch,
+        creator_id: CreatorAccountId,
+    ) -> Option<HashMap<FTAccountId, u128>> {
+        self.get_funding_goal(&epoch, &creator_id)
+    }
+
+    pub fn creator_funding_goal_met(&self, epoch: Epoch, creator_id: CreatorAccountId) -> bool {
+        self.is_funding_goal_met(&epoch, &creator_id)
+    }
+
+    /// Returns the copies of each NFT rank of `creator_account_id` left to pledge for, in `epoch`
+    #[handle_result]
+    pub fn tier_availability(
+        &self,
+        epoch: Epoch,
+        creator_account_id: CreatorAccountId,
+    ) -> Result<HashMap<UserNFTRank, u64>, MetaDaoError> {
+        self.get_tier_availability(&epoch, &creator_account_id)
+    }
+
+    /// Returns the balances of `creator_id`, per fungible token: the net proceeds raised over
+    /// all settled epochs, the amount withdrawn, and the claimable amount left
+    pub fn creator_balances(
+        &self,
+        creator_id: CreatorAccountId,
+    ) -> HashMap<FTAccountId, CreatorBalanceView> {
+        self.creator_balances
+            .get(&creator_id)
+            .unwrap_or_default()
+            .into_iter()
+            .map(|(ft_token_id, balance)| (ft_token_id, balance.into()))
+            .collect()
+    }
+
     // #[handle_result]
     // pub fn user_is_registered(&self, user_id: UserAccountId) -> Result<bool, MetaDaoError> {
     //     let votes_mapping = self